@@ -1,6 +1,9 @@
-use std::{fs, ops::{DerefMut, SubAssign, AddAssign, Div, Not}, borrow::BorrowMut};
-
-use itertools::Itertools;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    env,
+    fmt::{self, Write},
+    fs,
+};
 
 struct StringBox {
     content: String,
@@ -11,290 +14,363 @@ impl StringBox {
         self.content.lines().nth(y)?.chars().nth(x)
     }
 
-    fn find(&self, c: char) -> Point {
+    fn find(&self, c: char) -> Option<(usize, usize)> {
         for (y, line) in self.content.lines().enumerate() {
-            if let Some(x) = line.find(c) {
-                return Point {
-                    x,
-                    y,
-                    c,
-                    dir: Direction::UNKNOWN,
-                    count: 0,
-                };
+            if let Some(x) = line.chars().position(|l| l == c) {
+                return Some((x, y));
             }
         }
-        unreachable!("can't find char: {}", c);
+        None
     }
 
-    fn possible_moves(p: &Point) -> Vec<Move> {
-        use Direction::*;
+    // does the tile at (x, y) have a pipe opening towards its neighbour in `dir`
+    fn opens(&self, x: usize, y: usize, dir: Direction) -> bool {
+        self.get(x, y).and_then(openings).is_some_and(|o| o.contains(&dir))
+    }
 
-        match &p.dir {
-            NORTH => {
-                vec![Move { x: p.x, y: p.y - 1 }]
-            }
-            EAST => {
-                vec![Move { x: p.x + 1, y: p.y }]
-            }
-            SOUTH => {
-                vec![Move { x: p.x, y: p.y + 1 }]
-            }
-            WEST => {
-                vec![Move { x: p.x - 1, y: p.y }]
-            }
-            UNKNOWN => {
-                vec![
-                    Move { x: p.x + 1, y: p.y },
-                    Move { x: p.x - 1, y: p.y },
-                    Move { x: p.x, y: p.y + 1 },
-                    Move { x: p.x, y: p.y - 1 },
-                ]
-            }
+    // the pipe hidden under `S`, from the neighbours that connect back to it
+    fn start_pipe(&self, x: usize, y: usize) -> Result<char, LoopError> {
+        let connected: Vec<Direction> = Direction::ALL
+            .into_iter()
+            .filter(|d| d.step(x, y).is_some_and(|(nx, ny)| self.opens(nx, ny, d.opposite())))
+            .collect();
+
+        match connected.len() {
+            2 => Ok(pipe(connected[0], connected[1])),
+            0 | 1 => Err(LoopError::DeadEnd { x, y }),
+            connections => Err(LoopError::Branch { x, y, connections }),
         }
     }
 
-    fn permiteter(&self) -> Vec<Point> {
-        let mut points = vec![];
-        let start: Point = self.find('S');
-        points.push(start);
-        let mut next: Point = self.next(&start);
+    // the loop through `S`, in walking order starting at `S`
+    fn pipe_loop(&self) -> Result<PipeLoop, LoopError> {
+        let (start_x, start_y) = self.find('S').ok_or(LoopError::MissingStart)?;
+        let start = Point { x: start_x, y: start_y, c: self.start_pipe(start_x, start_y)? };
 
-        while next.c != 'S' {
-            points.push(next);
-            next = self.next(&next);
+        let mut points = vec![start];
+        let mut current = start;
+        let mut dir = openings(start.c).unwrap()[0];
+        loop {
+            let (x, y) = dir.step(current.x, current.y).ok_or(LoopError::DeadEnd { x: current.x, y: current.y })?;
+            if (x, y) == (start.x, start.y) {
+                return Ok(PipeLoop { points });
+            }
+            // the pipe we walk into has to open back towards where we came from
+            let [a, b] = self
+                .get(x, y)
+                .and_then(openings)
+                .filter(|o| o.contains(&dir.opposite()))
+                .ok_or(LoopError::DeadEnd { x: current.x, y: current.y })?;
+            dir = if a == dir.opposite() { b } else { a };
+            current = Point { x, y, c: self.get(x, y).unwrap() };
+            points.push(current);
         }
-        points
     }
+}
 
-    fn area(&self) -> f32 {
-        use Direction::*;
+#[derive(Debug, PartialEq, Eq)]
+enum LoopError {
+    MissingStart,
+    // the pipe at (x, y) doesn't lead into a connecting pipe
+    DeadEnd { x: usize, y: usize },
+    // `S` at (x, y) connects to more than two pipes
+    Branch { x: usize, y: usize, connections: usize },
+}
+
+impl fmt::Display for LoopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoopError::MissingStart => write!(f, "no start tile 'S'"),
+            LoopError::DeadEnd { x, y } => write!(f, "dead end at ({}, {})", x, y),
+            LoopError::Branch { x, y, connections } => {
+                write!(f, "start at ({}, {}) connects to {} pipes instead of 2", x, y, connections)
+            }
+        }
+    }
+}
 
-        let mut points = self.permiteter();
+struct PipeLoop {
+    // every tile of the loop in walking order, `S` is replaced by its pipe
+    points: Vec<Point>,
+}
 
+impl PipeLoop {
+    // tiles enclosed by the loop. The shoelace formula gives the area of the
+    // polygon through the tile centres and Pick's theorem (A = i + b/2 - 1)
+    // turns that into the number of tiles inside, i = A - b/2 + 1
+    fn area(&self) -> i64 {
+        let points = &self.points;
+        let boundary = points.len() as i64;
 
-        let furthest_point = points
+        let twice_area: i64 = points
             .iter()
-            .reduce(|a, b| {
-                if b.x >= a.x && b.y >= a.y {
-                    b
-                } else {
-                    a
-                }
-            });
-
-        match furthest_point {
-            Some(p) => {
-                assert_eq!(p.c, 'J');
-                match p.dir {
-                    Direction::NORTH => { 
-                        // counter clockwise, nothing to do
-                    }, 
-                    Direction::EAST => panic!("east"),
-                    Direction::SOUTH => panic!("south"),
-                    Direction::WEST => {
-                        // clockwise, need to flip
-
-                        points.reverse();
-                        for p in points.iter_mut() {
-                            p.flip();
-                        }
-                    },
-                    Direction::UNKNOWN => panic!("unknown"),
-                }
-            },
-            None => {
-                unreachable!("furthest point")
-            },
-        }
+            .zip(points.iter().cycle().skip(1))
+            .map(|(a, b)| a.x as i64 * b.y as i64 - b.x as i64 * a.y as i64)
+            .sum::<i64>()
+            .abs();
 
-        let (s_index, s) = points.iter().find_position(|p| p.c == 'S').unwrap();
-        let previous_index = (s_index - 1) % points.len();
-        let next_index = (s_index + 1) % points.len();
-        //assert_eq!(s_index, 0);
-        let previous = points.get(previous_index).unwrap();
-        let next = points.get(next_index).unwrap();
-
-        let (new_c, new_dir) = match (previous.dir, next.dir) {
-            (EAST, NORTH) => ('J', NORTH),
-            (EAST, SOUTH) => ('7', SOUTH),
-            (EAST, WEST) => {
-                match next.c {
-                    '7' => ('J', NORTH),
-                    'J' => ('7', SOUTH),
-                    _ => panic!("east")
-                }
-            },
-
-            (WEST, NORTH) => ('L', NORTH),
-            (WEST, SOUTH) => ('F', SOUTH),
-            (WEST, EAST) => {
-                match next.c {
-                    'F' => ('L', NORTH),
-                    'L' => ('F', SOUTH),
-                    _ => panic!("west")
-                }
-            },
-
-            (NORTH, EAST) => ('F', EAST),
-            (NORTH, WEST) => ('7', WEST),
-            (NORTH, SOUTH) => {
-                match next.c {
-                    '7' => ('F', EAST),
-                    'F' => ('7', WEST),
-                    _ => panic!("north")
-                }
-            },
-
-            (SOUTH, EAST) => ('L', EAST),
-            (SOUTH, WEST) => ('J', WEST),
-            (SOUTH, NORTH) => {
-                match next.c {
-                    '7' => ('J', WEST),
-                    'J' => ('7', EAST),
-                    _ => panic!("south")
-                }
-            },
+        (twice_area - boundary + 2) / 2
+    }
+}
 
-            (EAST, EAST) => ('-', EAST),
-            (WEST, WEST) => ('-', WEST),
-            (NORTH, NORTH) => ('|', NORTH),
-            (SOUTH, SOUTH) => ('|', SOUTH),
-            _ => panic!("ee {:?}, {:?}", previous, next)
-        };
+// every pipe on the map, `S` as the pipe it stands for. When that can't be
+// inferred `S` is left out
+struct PipeField {
+    pipes: HashMap<(usize, usize), char>,
+    start: Option<(usize, usize)>,
+}
 
-        let s = points.get_mut(s_index).unwrap();
-        s.c = new_c;
-        s.dir = new_dir;
-
-        // get offset of points by 0.5 in
-        let mut offset_points: Vec<Vec2f> = points.into_iter().map(|p| {
-            let x: f32 = p.x as f32;
-            let y: f32 = p.y as f32;
-            match (p.c, p.dir) {
-                ('L', EAST) => Vec2f {x: x + 0.5, y: y - 0.5},
-                ('L', NORTH) => Vec2f {x: x - 0.5, y: y + 0.5},
-                ('F', SOUTH) => Vec2f {x: x + 0.5, y: y + 0.5},
-                ('F', EAST) => Vec2f {x: x - 0.5, y: y - 0.5},
-                ('J', NORTH) => Vec2f {x: -0.5 + x, y: -0.5 + y},
-                ('J', WEST) => Vec2f {x: 0.5 + x, y: 0.5 + y},
-                ('7', WEST) => Vec2f {x: -0.5 + x, y: 0.5 + y},
-                ('7', SOUTH) => Vec2f {x: 0.5 + x, y: -0.5 + y},
-                ('|', NORTH) => Vec2f {x: -0.5 + x, y},
-                ('|', SOUTH) => Vec2f {x: 0.5 + x, y},
-                ('-', EAST) => Vec2f {x: 0.0 + x, y: -0.5 + y},
-                ('-', WEST) => Vec2f {x: 0.0 + x, y: 0.5 + y},
-                _ => panic!("ee {:?}", p)
+enum Component {
+    Loop(PipeLoop),
+    // pipes connected one after the other, from one open end to the other
+    Chain(Vec<Point>),
+}
+
+impl PipeField {
+    fn new(sb: &StringBox) -> Self {
+        let mut pipes = HashMap::new();
+        let mut start = None;
+        for (y, line) in sb.content.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if c == 'S' {
+                    start = Some((x, y));
+                    if let Ok(pipe) = sb.start_pipe(x, y) {
+                        pipes.insert((x, y), pipe);
+                    }
+                } else if openings(c).is_some() {
+                    pipes.insert((x, y), c);
+                }
             }
-        }).collect();
+        }
+        PipeField { pipes, start }
+    }
 
-        let first = offset_points.first().unwrap();
-        let last = offset_points.last().unwrap();
+    // neighbours whose pipes open towards each other
+    fn connected(&self, (x, y): (usize, usize)) -> Vec<(usize, usize)> {
+        let Some(c) = self.pipes.get(&(x, y)) else {
+            return vec![];
+        };
+        openings(*c)
+            .unwrap()
+            .into_iter()
+            .filter_map(|d| {
+                let next = d.step(x, y)?;
+                let back = openings(*self.pipes.get(&next)?)?;
+                back.contains(&d.opposite()).then_some(next)
+            })
+            .collect()
+    }
 
-        // shoelace formula to get area
-        (last.x * first.y - first.x * last.y + offset_points.windows(2).map(|p| {
-            let a = &p[0];
-            let b = &p[1];
-            a.x * b.y - b.x * a.y
-        }).sum::<f32>()).div(2.0).abs()
+    fn point(&self, (x, y): (usize, usize)) -> Point {
+        Point { x, y, c: self.pipes[&(x, y)] }
     }
 
-    fn next(&self, p: &Point) -> Point {
-        use Direction::*;
+    // tiles are at most connected to two others, so every component is
+    // either a loop or a chain. Components come in the order of their top
+    // left tile
+    fn components(&self) -> Vec<Component> {
+        let mut tiles: Vec<(usize, usize)> = self.pipes.keys().copied().collect();
+        tiles.sort_by_key(|(x, y)| (*y, *x));
+
+        let mut seen = HashSet::new();
+        let mut components = Vec::new();
+        for tile in tiles {
+            if seen.contains(&tile) {
+                continue;
+            }
+            let members: Vec<(usize, usize)> = self.distances(tile).into_keys().collect();
+            let is_loop = members.iter().all(|m| self.connected(*m).len() == 2);
+            // walk from an open end, or from the top left of a loop
+            let first = members
+                .iter()
+                .filter(|m| is_loop || self.connected(**m).len() < 2)
+                .min_by_key(|(x, y)| (*y, *x))
+                .copied()
+                .unwrap();
+
+            let mut points = vec![self.point(first)];
+            seen.insert(first);
+            let mut current = first;
+            while let Some(next) = self.connected(current).into_iter().find(|n| !seen.contains(n)) {
+                points.push(self.point(next));
+                seen.insert(next);
+                current = next;
+            }
 
-        let moves = Self::possible_moves(&p);
-
-        for m in moves {
-            let next_char = self.get(m.x, m.y);
-            let next_direction = match (next_char, &p.dir) {
-                (Some('|'), NORTH | SOUTH) => Some(p.dir),
-                (Some('|'), UNKNOWN) if m.y > p.y => Some(SOUTH),
-                (Some('|'), UNKNOWN) if m.y < p.y => Some(NORTH),
-                (Some('-'), WEST | EAST) => Some(p.dir),
-                (Some('-'), UNKNOWN) if m.x > p.x => Some(EAST),
-                (Some('-'), UNKNOWN) if m.x < p.x => Some(WEST),
-                (Some('L'), UNKNOWN) if m.x < p.x => Some(NORTH),
-                (Some('L'), UNKNOWN) if m.y < p.y => Some(EAST),
-                (Some('L'), WEST) => Some(NORTH),
-                (Some('L'), SOUTH) => Some(EAST),
-                (Some('J'), UNKNOWN) if m.x > p.x => Some(NORTH),
-                (Some('J'), UNKNOWN) if m.y < p.y => Some(WEST),
-                (Some('J'), SOUTH) => Some(WEST),
-                (Some('J'), EAST) => Some(NORTH),
-                (Some('7'), UNKNOWN) if m.x > p.x => Some(SOUTH),
-                (Some('7'), UNKNOWN) if m.y > p.y => Some(WEST),
-                (Some('7'), NORTH) => Some(WEST),
-                (Some('7'), EAST) => Some(SOUTH),
-                (Some('F'), UNKNOWN) if m.x < p.x => Some(SOUTH),
-                (Some('F'), UNKNOWN) if m.y > p.y => Some(EAST),
-                (Some('F'), NORTH) => Some(EAST),
-                (Some('F'), WEST) => Some(SOUTH),
-                // this will terminate it
-                (Some('S'), _) => Some(UNKNOWN),
-                _ => None,
-            };
-            if let Some(dir) = next_direction {
-                return Point {
-                    x: m.x,
-                    y: m.y,
-                    c: next_char.unwrap(),
-                    dir,
-                    count: p.count + 1,
-                };
+            components.push(if is_loop { Component::Loop(PipeLoop { points }) } else { Component::Chain(points) });
+        }
+        components
+    }
+
+    // steps to every tile reachable from `from` along the pipes
+    fn distances(&self, from: (usize, usize)) -> HashMap<(usize, usize), usize> {
+        let mut distances = HashMap::from([(from, 0)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(tile) = queue.pop_front() {
+            let distance = distances[&tile];
+            for next in self.connected(tile) {
+                distances.entry(next).or_insert_with(|| {
+                    queue.push_back(next);
+                    distance + 1
+                });
             }
         }
-        unreachable!("should always find anther");
+        distances
+    }
+
+    // steps to the tile furthest away from `S`
+    fn farthest(&self) -> Option<usize> {
+        let start = self.start?;
+        if !self.pipes.contains_key(&start) {
+            return None;
+        }
+        self.distances(start).into_values().max()
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-struct Vec2f {
-    x: f32,
-    y: f32,
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Tile {
+    // part of the loop, with the pipe it is made of
+    Loop(char),
+    Inside,
+    Outside,
 }
 
-impl std::ops::Sub for Vec2f {
-    type Output = Self;
+struct TileMap {
+    tiles: Vec<Vec<Tile>>,
+}
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        Self {x: self.x - rhs.x, y: self.y - rhs.y}
+impl TileMap {
+    // walking a row from the left, every loop pipe opening to the north
+    // crosses the loop once, so a tile is inside after an odd number of them.
+    // Counting only one of the north and south openings makes `L-7` cross once
+    // and `L-J` not at all
+    fn new(sb: &StringBox, pipe_loop: &PipeLoop) -> Self {
+        let pipes: HashMap<(usize, usize), char> = pipe_loop.points.iter().map(|p| ((p.x, p.y), p.c)).collect();
+
+        let tiles = sb
+            .content
+            .lines()
+            .enumerate()
+            .map(|(y, line)| {
+                let mut inside = false;
+                (0..line.chars().count())
+                    .map(|x| match pipes.get(&(x, y)) {
+                        Some(c) => {
+                            if matches!(c, '|' | 'L' | 'J') {
+                                inside = !inside;
+                            }
+                            Tile::Loop(*c)
+                        }
+                        None if inside => Tile::Inside,
+                        None => Tile::Outside,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        TileMap { tiles }
     }
-}
 
-impl std::ops::Add for Vec2f {
-    type Output = Self;
+    fn enclosed(&self) -> BTreeSet<(usize, usize)> {
+        let mut enclosed = BTreeSet::new();
+        for (y, row) in self.tiles.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                if *tile == Tile::Inside {
+                    enclosed.insert((x, y));
+                }
+            }
+        }
+        enclosed
+    }
 
-    fn add(self, rhs: Self) -> Self::Output {
-        Self {x: self.x + rhs.x, y: self.y + rhs.y}
+    // the loop in box drawing characters, inside tiles as `I` and outside
+    // tiles as `O`. With `colour` the tiles are coloured with ansi escapes
+    fn render(&self, colour: bool) -> String {
+        let mut out = String::new();
+        for row in &self.tiles {
+            for tile in row {
+                let (code, c) = match tile {
+                    Tile::Loop(c) => ("33", box_drawing(*c)),
+                    Tile::Inside => ("1;32", 'I'),
+                    Tile::Outside => ("2", 'O'),
+                };
+                if colour {
+                    write!(out, "\x1b[{}m{}\x1b[0m", code, c).unwrap();
+                } else {
+                    out.push(c);
+                }
+            }
+            out.push('\n');
+        }
+        out
     }
 }
 
-impl SubAssign for Vec2f {
-    fn sub_assign(&mut self, rhs: Self) {
-        self.x -= rhs.x;
-        self.y -= rhs.y;
+fn box_drawing(c: char) -> char {
+    match c {
+        '|' => '│',
+        '-' => '─',
+        'L' => '└',
+        'J' => '┘',
+        '7' => '┐',
+        'F' => '┌',
+        _ => c,
     }
 }
 
-impl AddAssign for Vec2f {
-    fn add_assign(&mut self, rhs: Self) {
-        self.x += rhs.x;
-        self.y += rhs.y;
-    }
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Direction {
+    North,
+    East,
+    South,
+    West,
 }
 
+impl Direction {
+    const ALL: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
 
-struct Move {
-    x: usize,
-    y: usize,
+    fn opposite(self) -> Direction {
+        use Direction::*;
+        match self {
+            North => South,
+            East => West,
+            South => North,
+            West => East,
+        }
+    }
+
+    // neighbour of (x, y), None when it would be off the top or left edge
+    fn step(self, x: usize, y: usize) -> Option<(usize, usize)> {
+        use Direction::*;
+        match self {
+            North => Some((x, y.checked_sub(1)?)),
+            East => Some((x + 1, y)),
+            South => Some((x, y + 1)),
+            West => Some((x.checked_sub(1)?, y)),
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum Direction {
-    NORTH,
-    EAST,
-    SOUTH,
-    WEST,
-    UNKNOWN,
+// the two sides a pipe opens to, None for ground and anything else
+fn openings(c: char) -> Option<[Direction; 2]> {
+    use Direction::*;
+    match c {
+        '|' => Some([North, South]),
+        '-' => Some([East, West]),
+        'L' => Some([North, East]),
+        'J' => Some([North, West]),
+        '7' => Some([South, West]),
+        'F' => Some([South, East]),
+        _ => None,
+    }
+}
+
+fn pipe(a: Direction, b: Direction) -> char {
+    ['|', '-', 'L', 'J', '7', 'F']
+        .into_iter()
+        .find(|c| openings(*c).is_some_and(|o| o.contains(&a) && o.contains(&b)))
+        .unwrap()
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -302,48 +378,56 @@ struct Point {
     x: usize,
     y: usize,
     c: char,
-    // direction where we came from
-    dir: Direction,
-    count: usize,
-}
-
-impl Point {
-    fn flip(&mut self) {
-        use Direction::*;
-        match (self.c, self.dir) {
-            ('F', EAST) => self.dir = SOUTH,
-            ('F', _) => self.dir = EAST,
-            ('L', EAST) => self.dir = NORTH,
-            ('L', _) => self.dir = EAST,
-            ('J', NORTH) => self.dir = WEST,
-            ('J', _) => self.dir = NORTH,
-            ('7', SOUTH) => self.dir = WEST,
-            ('7', _) => self.dir = SOUTH,
-            ('-', WEST) => self.dir = EAST,
-            ('-', EAST) => self.dir = WEST,
-            ('|', NORTH) => self.dir = SOUTH,
-            ('|', SOUTH) => self.dir = NORTH,
-            ('S', _) => {}, // ignore
-            _ => panic!("point flip error {:?}", self)
-        }
-    }
 }
 
 fn part1() -> usize {
     let content = fs::read_to_string("src/23/day10/input.txt").unwrap();
 
     let sb = StringBox { content };
-    num::Integer::div_ceil(&(sb.permiteter().last().unwrap().count), &2)
+    PipeField::new(&sb).farthest().unwrap()
 }
 
-fn part2() -> f32 {
+fn part2() -> i64 {
     let content = fs::read_to_string("src/23/day10/input.txt").unwrap();
 
     let sb = StringBox { content };
-    sb.area()
+    sb.pipe_loop().unwrap().area()
 }
 
 fn main() {
+    // `23-day10 <render|enclosed|components> <file>` draws the classified map,
+    // lists the enclosed tiles as `x,y` or lists every loop and chain of pipes
+    let args: Vec<String> = env::args().collect();
+    if let [_, command, path] = args.as_slice() {
+        let sb = StringBox { content: fs::read_to_string(path).unwrap() };
+        if command == "components" {
+            for component in PipeField::new(&sb).components() {
+                match component {
+                    Component::Loop(l) => {
+                        let p = l.points[0];
+                        println!("loop at ({}, {}): length {}, area {}", p.x, p.y, l.points.len(), l.area());
+                    }
+                    Component::Chain(c) => {
+                        let (first, last) = (c[0], c[c.len() - 1]);
+                        println!("chain ({}, {}) to ({}, {}): length {}", first.x, first.y, last.x, last.y, c.len());
+                    }
+                }
+            }
+            return;
+        }
+        let pipe_loop = match sb.pipe_loop() {
+            Ok(pipe_loop) => pipe_loop,
+            Err(e) => return eprintln!("{}", e),
+        };
+        let map = TileMap::new(&sb, &pipe_loop);
+        match command.as_str() {
+            "render" => print!("{}", map.render(true)),
+            "enclosed" => map.enclosed().iter().for_each(|(x, y)| println!("{},{}", x, y)),
+            _ => eprintln!("unknown command: {}", command),
+        }
+        return;
+    }
+
     println!("p1: {}", part1());
     println!("p2: {}", part2());
 }
@@ -352,7 +436,7 @@ fn main() {
 mod test {
     use test_case::test_case;
 
-    use crate::{Direction::*, Point, StringBox};
+    use crate::{Component, LoopError, PipeField, Point, StringBox, TileMap};
 
     #[test_case(".....\n.S-7.\n.|.|.\n.L-J.\n.....\n")]
     fn test_get(lines: &str) {
@@ -363,45 +447,42 @@ mod test {
         assert_eq!(sb.get(1, 1).unwrap(), 'S');
     }
 
-    #[test_case(".....\n.S-7.\n.|.|.\n.L-J.\n.....\n")]
-    fn test_next(lines: &str) {
+    #[test_case(".....\n.S-7.\n.|.|.\n.L-J.\n.....\n", 'F')]
+    #[test_case("..F7.\n.FJ|.\nSJ.L7\n|F--J\nLJ...", 'F')]
+    #[test_case("F-7\n|.|\nL-S", 'J')]
+    #[test_case("F-7\nS.|\nL-J", '|')]
+    #[test_case("FS7\n|.|\nL-J", '-')]
+    // a pipe pointing at `S` that doesn't connect back doesn't count
+    #[test_case("-L|F7\n7S-7|\nL|7||\n-L-J|\nL|-JF", 'F')]
+    fn test_start_pipe(lines: &str, c: char) {
         let sb = StringBox {
             content: lines.to_owned(),
         };
-        let start: Point = sb.find('S');
-        assert_eq!(
-            start,
-            Point {
-                x: 1,
-                y: 1,
-                c: 'S',
-                dir: UNKNOWN,
-                count: 0
-            }
-        );
+        let (x, y) = sb.find('S').unwrap();
+        assert_eq!(sb.start_pipe(x, y), Ok(c));
+        assert_eq!(sb.pipe_loop().unwrap().points[0], Point { x, y, c });
+    }
 
-        let mut next = sb.next(&start);
-        assert_eq!(
-            next,
-            Point {
-                x: 2,
-                y: 1,
-                c: '-',
-                dir: EAST,
-                count: 1
-            }
-        );
-        next = sb.next(&next);
-        assert_eq!(
-            next,
-            Point {
-                x: 3,
-                y: 1,
-                c: '7',
-                dir: SOUTH,
-                count: 2
-            }
-        );
+    #[test]
+    fn test_pipe_loop() {
+        let sb = StringBox {
+            content: ".....\n.S-7.\n.|.|.\n.L-J.\n.....\n".to_owned(),
+        };
+        let points: Vec<(usize, usize)> = sb.pipe_loop().unwrap().points.iter().map(|p| (p.x, p.y)).collect();
+        assert_eq!(points, vec![(1, 1), (1, 2), (1, 3), (2, 3), (3, 3), (3, 2), (3, 1), (2, 1)]);
+    }
+
+    #[test_case("...\n...", LoopError::MissingStart)]
+    #[test_case("S-7\n|.|\n|.J", LoopError::DeadEnd { x: 0, y: 2 })]
+    #[test_case("S-7\n..|\nL-J", LoopError::DeadEnd { x: 0, y: 0 })]
+    #[test_case("F-7\n|.|\nS-.", LoopError::DeadEnd { x: 2, y: 1 })]
+    #[test_case(".|.\n-S-\n.|.", LoopError::Branch { x: 1, y: 1, connections: 4 })]
+    #[test_case("F7.\nS-.\n|..", LoopError::Branch { x: 0, y: 1, connections: 3 })]
+    fn test_loop_errors(lines: &str, error: LoopError) {
+        let sb = StringBox {
+            content: lines.to_owned(),
+        };
+        assert_eq!(sb.pipe_loop().err(), Some(error));
     }
 
     //..F7F7
@@ -417,22 +498,11 @@ mod test {
         let sb = StringBox {
             content: lines.to_owned(),
         };
-        let mut next: Point = sb.find('S');
-
-        loop {
-            println!("{:?}", next);
-            next = sb.next(&next);
-            if next.c == 'S' {
-                println!("break");
-                break;
-            }
-        }
-
-        assert_eq!(num::Integer::div_ceil(&(next.count - 1), &2), count);
+        assert_eq!(PipeField::new(&sb).farthest(), Some(count));
     }
 
-    
-    // problem is `x` are counted
+    // the tiles marked `x` are surrounded by pipes but lie outside the loop,
+    // only the four tiles in the bottom pockets are enclosed
     //...........
     //.S-------7.
     //.|F-----7|.
@@ -442,14 +512,80 @@ mod test {
     //.|..|.|..|.
     //.L--J.L--J.
     //...........
-    #[test_case("...........\n.S-------7.\n.|F-----7|.\n.||.....||.\n.||.....||.\n.|L-7.F-J|.\n.|..|.|..|.\n.L--J.L--J.\n...........", 4.0)]
-    #[test_case(".....\n.S-7.\n.|.|.\n.L-J.\n.....\n", 1.0)]
-    #[test_case("..\n.S7\n.LJ", 0.0)]
-    fn test_area(lines: &str, area: f32) {
+    #[test_case("...........\n.S-------7.\n.|F-----7|.\n.||.....||.\n.||.....||.\n.|L-7.F-J|.\n.|..|.|..|.\n.L--J.L--J.\n...........", 4)]
+    #[test_case(".....\n.S-7.\n.|.|.\n.L-J.\n.....\n", 1)]
+    #[test_case("..\n.S7\n.LJ", 0)]
+    // the loop doesn't have to end in a bottom right J
+    #[test_case("S-7\n|.|\nL-J", 1)]
+    #[test_case("F-7\n|.|\nS-J", 1)]
+    #[test_case("F-S\n|.|\nL-J", 1)]
+    #[test_case("F7.\n|L7\nS-J", 0)]
+    #[test_case(".F----7F7F7F7F-7....\n.|F--7||||||||FJ....\n.||.FJ||||||||L7....\nFJL7L7LJLJ||LJ.L-7..\nL--J.L7...LJS7F-7L7.\n....F-J..F7FJ|L7L7L7\n....L7.F7||L7|.L7L7|\n.....|FJLJ|FJ|F7|.LJ\n....FJL-7.||.||||...\n....L---J.LJ.LJLJ...", 8)]
+    fn test_area(lines: &str, area: i64) {
         let sb = StringBox {
             content: lines.to_owned(),
         };
 
-        assert_eq!(sb.area(), area);
+        let pipe_loop = sb.pipe_loop().unwrap();
+        assert_eq!(pipe_loop.area(), area);
+        assert_eq!(TileMap::new(&sb, &pipe_loop).enclosed().len() as i64, area);
+    }
+
+    #[test]
+    fn test_tile_map() {
+        // the tiles squeezed between pipes are outside
+        let sb = StringBox {
+            content: "..........\n.S------7.\n.|F----7|.\n.||....||.\n.||....||.\n.|L-7F-J|.\n.|..||..|.\n.L--JL--J.\n..........".to_owned(),
+        };
+        let map = TileMap::new(&sb, &sb.pipe_loop().unwrap());
+        assert_eq!(map.enclosed().into_iter().collect::<Vec<_>>(), vec![(2, 6), (3, 6), (6, 6), (7, 6)]);
+        assert_eq!(
+            map.render(false),
+            "OOOOOOOOOO\nO┌──────┐O\nO│┌────┐│O\nO││OOOO││O\nO││OOOO││O\nO│└─┐┌─┘│O\nO│II││II│O\nO└──┘└──┘O\nOOOOOOOOOO\n"
+        );
+        assert!(map.render(true).contains("\x1b[1;32mI\x1b[0m"));
+    }
+
+    #[test]
+    fn test_components() {
+        // a loop around S, a loop without S, a chain and a pipe on its own
+        let sb = StringBox {
+            content: "S-7.F7\n|.|.LJ\nL-J.-L\n.F-7..\n......\n|.....".to_owned(),
+        };
+        let field = PipeField::new(&sb);
+        let summary: Vec<(bool, usize, (usize, usize))> = field
+            .components()
+            .iter()
+            .map(|c| match c {
+                Component::Loop(l) => (true, l.points.len(), (l.points[0].x, l.points[0].y)),
+                Component::Chain(c) => (false, c.len(), (c[0].x, c[0].y)),
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (true, 8, (0, 0)),
+                (true, 4, (4, 0)),
+                (false, 1, (4, 2)),
+                (false, 1, (5, 2)),
+                (false, 3, (1, 3)),
+                (false, 1, (0, 5)),
+            ]
+        );
+        match &field.components()[0] {
+            Component::Loop(l) => assert_eq!(l.area(), 1),
+            Component::Chain(_) => unreachable!(),
+        }
+        assert_eq!(field.farthest(), Some(4));
+    }
+
+    #[test]
+    fn test_farthest_open_start() {
+        // S only reaches along a chain, so the furthest tile is its end
+        let sb = StringBox {
+            content: "S--7\n|..|\n....".to_owned(),
+        };
+        assert_eq!(PipeField::new(&sb).farthest(), Some(4));
+        assert_eq!(PipeField::new(&StringBox { content: ".|.\n-S-\n.|.".to_owned() }).farthest(), None);
     }
 }
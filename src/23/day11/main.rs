@@ -1,6 +1,4 @@
-use std::fs;
-
-use itertools::Itertools;
+use std::{collections::BinaryHeap, env, fmt::Write, fs};
 
 #[derive(Debug, PartialEq, Eq)]
 enum Expand {
@@ -8,69 +6,138 @@ enum Expand {
     Row(usize)
 }
 
-struct Universe {
-    expand_list: Vec<Expand>,
-    galaxies: Vec<Galaxy>
+// one bit per row or column, set when it holds a galaxy
+#[derive(Debug, Default)]
+struct Occupancy {
+    bits: Vec<u64>,
+    len: usize,
 }
 
+impl Occupancy {
+    fn insert(&mut self, i: usize) {
+        if i / 64 >= self.bits.len() {
+            self.bits.resize(i / 64 + 1, 0);
+        }
+        self.bits[i / 64] |= 1 << (i % 64);
+    }
+
+    fn contains(&self, i: usize) -> bool {
+        self.bits.get(i / 64).is_some_and(|b| b & (1 << (i % 64)) != 0)
+    }
+
+    fn empty(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).filter(|i| !self.contains(*i))
+    }
 
+    // how many empty ones come before each index
+    fn empty_before(&self) -> Vec<usize> {
+        let mut count = 0;
+        (0..self.len)
+            .map(|i| {
+                let before = count;
+                if !self.contains(i) {
+                    count += 1;
+                }
+                before
+            })
+            .collect()
+    }
+}
+
+// every empty column grows into `columns` columns and every empty row into
+// `rows` rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Expansion {
+    columns: usize,
+    rows: usize,
+}
+
+impl Expansion {
+    fn uniform(factor: usize) -> Self {
+        Expansion { columns: factor, rows: factor }
+    }
+}
+
+struct Universe {
+    columns: Occupancy,
+    rows: Occupancy,
+    galaxies: Vec<Galaxy>
+}
 
 impl Universe {
-    fn new(content: String, expansion_size: usize) -> Self {
-        let mut expand_list = Vec::new();
+    fn new(content: String, expansion_factor: usize) -> Self {
+        Self::with_expansion(&content, Expansion::uniform(expansion_factor))
+    }
+
+    fn with_expansion(content: &str, expansion: Expansion) -> Self {
+        let mut columns = Occupancy::default();
+        let mut rows = Occupancy::default();
+        let mut found = Vec::new();
 
         for (y, line) in content.lines().enumerate() {
-            if line.chars().all_equal() {
-                expand_list.push(Expand::Row(y));
+            rows.len = y + 1;
+            for (x, c) in line.chars().enumerate() {
+                columns.len = columns.len.max(x + 1);
+                if c == '#' {
+                    columns.insert(x);
+                    rows.insert(y);
+                    found.push((x, y));
+                }
             }
         }
 
-        for x in 0..content.lines().nth(0).unwrap().len() {
-            for y in 0..content.lines().count() {
-                let a = content.lines().nth(y).unwrap().chars().nth(x).unwrap();
-                if a == '#' {
-                    break; 
-                }
-                // if last item
-                if y + 1 == content.lines().count() {
-                    expand_list.push(Expand::Column(x));
+        let columns_before = columns.empty_before();
+        let rows_before = rows.empty_before();
+        let galaxies = found
+            .into_iter()
+            .map(|(x, y)| Galaxy {
+                x: x + columns_before[x] * expansion.columns - columns_before[x],
+                y: y + rows_before[y] * expansion.rows - rows_before[y],
+            })
+            .collect();
+
+        Universe { columns, rows, galaxies }
+    }
+
+    fn expand_list(&self) -> Vec<Expand> {
+        self.rows.empty().map(Expand::Row).chain(self.columns.empty().map(Expand::Column)).collect()
+    }
+
+    // index of the other galaxy that is the closest (or furthest with
+    // `farthest`) to galaxy `i`, the first one on ties
+    fn nearest(&self, i: usize, metric: Metric) -> Option<usize> {
+        let others = (0..self.galaxies.len()).filter(|j| *j != i);
+        others.min_by_key(|j| metric.key(&self.galaxies[i], &self.galaxies[*j]))
+    }
+
+    fn farthest(&self, i: usize, metric: Metric) -> Option<usize> {
+        let others = (0..self.galaxies.len()).filter(|j| *j != i);
+        others.rev().max_by_key(|j| metric.key(&self.galaxies[i], &self.galaxies[*j]))
+    }
+
+    // the k closest pairs of galaxies as indices, closest first. Only the
+    // k best are kept in a max heap, so this doesn't collect every pair
+    fn closest_pairs(&self, k: usize, metric: Metric) -> Vec<(usize, usize)> {
+        let mut heap = BinaryHeap::new();
+        for i in 0..self.galaxies.len() {
+            for j in i + 1..self.galaxies.len() {
+                heap.push((metric.key(&self.galaxies[i], &self.galaxies[j]), i, j));
+                if heap.len() > k {
+                    heap.pop();
                 }
             }
         }
-
-        let galaxies = Self::galaxies(content, &expand_list, expansion_size);
-        Universe { expand_list, galaxies }
+        heap.into_sorted_vec().into_iter().map(|(_, i, j)| (i, j)).collect()
     }
 
-    fn galaxies(content: String, expands: &Vec<Expand>, expansion_size: usize) -> Vec<Galaxy> {
-        content.lines().enumerate().map(|(y, line)| {
-            line.chars().enumerate().filter_map(|(x, c)| {
-                if c == '#' {
-                    // get quantity of expands which have less than x and y
-                    let (columns, rows) = expands.iter().filter_map(|e| match e {
-                        Expand::Column(e_x) => {
-                            if e_x < &x {
-                                Some((1, 0))
-                            } else {
-                                None
-                            }
-                        },
-                        Expand::Row(e_y) => {
-                            if e_y < &y {
-                                Some((0, 1))
-                            } else {
-                                None
-                            }
-                        },
-                    }).reduce(|acc, e| {
-                        (acc.0 + e.0, acc.1 + e.1)
-                    }).unwrap_or((0, 0));
-                    Some(Galaxy {x: x + columns * expansion_size, y: y + rows * expansion_size})
-                } else {
-                    None
-                }
-            }).collect::<Vec<Galaxy>>()
-        }).flatten().collect()
+    // distances between every two galaxies as csv, one row per galaxy
+    fn distance_matrix(&self, metric: Metric) -> String {
+        let mut out = String::new();
+        for a in &self.galaxies {
+            let row: Vec<String> = self.galaxies.iter().map(|b| metric.distance(a, b).to_string()).collect();
+            writeln!(out, "{}", row.join(",")).unwrap();
+        }
+        out
     }
 }
 
@@ -86,34 +153,114 @@ impl Galaxy {
     }
 }
 
-fn pairs<T: Copy>(v: Vec<T>) -> Vec<(T, T)> {
-    v.iter().enumerate().map(|(i, e)| {
-        v[i+1..v.len()].iter().map(|other| {
-            (*e, *other)
-        })
-    }).flatten().collect()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+    Manhattan,
+    Chebyshev,
+    Euclidean,
+}
+
+impl Metric {
+    fn named(name: &str) -> Option<Self> {
+        match name {
+            "manhattan" => Some(Metric::Manhattan),
+            "chebyshev" => Some(Metric::Chebyshev),
+            "euclidean" => Some(Metric::Euclidean),
+            _ => None,
+        }
+    }
+
+    // orders galaxy pairs the same way as `distance` but stays exact, the
+    // euclidean distance is compared by its square
+    fn key(&self, a: &Galaxy, b: &Galaxy) -> u128 {
+        let dx = a.x.abs_diff(b.x) as u128;
+        let dy = a.y.abs_diff(b.y) as u128;
+        match self {
+            Metric::Manhattan => a.cost(b) as u128,
+            Metric::Chebyshev => dx.max(dy),
+            Metric::Euclidean => dx * dx + dy * dy,
+        }
+    }
+
+    fn distance(&self, a: &Galaxy, b: &Galaxy) -> f64 {
+        match self {
+            Metric::Euclidean => (self.key(a, b) as f64).sqrt(),
+            _ => self.key(a, b) as f64,
+        }
+    }
+}
+
+// sum of |a - b| over every pair of values. Once sorted every value is at
+// least the ones before it, so the i-th one adds i * value minus their sum
+fn axis_distance_sum(mut values: Vec<usize>) -> u128 {
+    values.sort_unstable();
+
+    let mut before: u128 = 0;
+    let mut total: u128 = 0;
+    for (i, value) in values.into_iter().enumerate() {
+        total += i as u128 * value as u128 - before;
+        before += value as u128;
+    }
+    total
+}
+
+// manhattan distances split into x and y, so every axis can be summed on its own
+fn distance_sum(galaxies: &[Galaxy]) -> u128 {
+    axis_distance_sum(galaxies.iter().map(|g| g.x).collect())
+        + axis_distance_sum(galaxies.iter().map(|g| g.y).collect())
 }
 
-fn part1() -> usize {
+fn part1() -> u128 {
     let lines = fs::read_to_string("src/23/day11/input.txt").unwrap();
 
-    let u = Universe::new(lines, 1);
+    let u = Universe::new(lines, 2);
 
-    pairs(u.galaxies).iter().map(|(a, b)| a.cost(b)).sum()
+    distance_sum(&u.galaxies)
 }
 
-fn part2() -> usize {
+fn part2() -> u128 {
     let lines = fs::read_to_string("src/23/day11/input.txt").unwrap();
 
-    let u = Universe::new(lines, 999999);
+    let u = Universe::new(lines, 1_000_000);
 
-    pairs(u.galaxies).iter().map(|(a, b)| a.cost(b)).sum()
+    distance_sum(&u.galaxies)
 }
 
 
 fn main() {
+    // `23-day11 <nearest|pairs|matrix> <metric> <factor> <file>` queries the
+    // universe expanded by `factor` under a manhattan, chebyshev or euclidean metric
+    let args: Vec<String> = env::args().collect();
+    if let [_, query, metric, factor, path] = args.as_slice() {
+        let metric = Metric::named(metric).expect("unknown metric");
+        let universe = Universe::new(fs::read_to_string(path).unwrap(), factor.parse().unwrap());
+        let g = &universe.galaxies;
+        match query.as_str() {
+            "nearest" => {
+                for i in 0..g.len() {
+                    if let (Some(n), Some(f)) = (universe.nearest(i, metric), universe.farthest(i, metric)) {
+                        println!("{:?}: nearest {:?} at {}, farthest {:?} at {}", g[i], g[n], metric.distance(&g[i], &g[n]), g[f], metric.distance(&g[i], &g[f]));
+                    }
+                }
+            }
+            "pairs" => {
+                for (i, j) in universe.closest_pairs(10, metric) {
+                    println!("{:?} {:?}: {}", g[i], g[j], metric.distance(&g[i], &g[j]));
+                }
+            }
+            "matrix" => print!("{}", universe.distance_matrix(metric)),
+            _ => eprintln!("unknown query: {}", query),
+        }
+        return;
+    }
+
     println!("p1: {}", part1());
     println!("p2: {}", part2());
+
+    let lines = fs::read_to_string("src/23/day11/input.txt").unwrap();
+    let expand_list = Universe::new(lines, 2).expand_list();
+    let rows = expand_list.iter().filter(|e| matches!(e, Expand::Row(_))).count();
+    println!("empty rows: {}, empty columns: {}", rows, expand_list.len() - rows);
 }
 
 #[cfg(test)]
@@ -121,17 +268,51 @@ mod test {
     use test_case::test_case;
     use crate::Expand;
     use crate::Expand::*;
+    use crate::Expansion;
+    use crate::Metric;
 
     use crate::Galaxy;
     use crate::Universe;
-    use crate::pairs;
+    use crate::distance_sum;
+
+    fn pairs<T: Copy>(v: Vec<T>) -> Vec<(T, T)> {
+        v.iter().enumerate().flat_map(|(i, e)| {
+            v[i+1..v.len()].iter().map(|other| {
+                (*e, *other)
+            })
+        }).collect()
+    }
+
+    const EXAMPLE: &str = "...#......\n.......#..\n#.........\n..........\n......#...\n.#........\n.........#\n..........\n.......#..\n#...#.....";
 
     #[test_case("..\n..", vec![Row(0), Row(1), Column(0), Column(1)])]
     #[test_case("#.\n.#", vec![])]
     #[test_case("..\n.#", vec![Row(0), Column(0)])]
     fn test_universe(lines: &str, expand_list: Vec<Expand>) {
         let universe = Universe::new(lines.to_owned(), 2);
-        assert_eq!(universe.expand_list, expand_list)
+        assert_eq!(universe.expand_list(), expand_list)
+    }
+
+    // a row or column full of galaxies isn't empty
+    #[test_case("##\n..", vec![Row(1)])]
+    #[test_case("#.\n#.", vec![Column(1)])]
+    // short lines are padded with empty space
+    #[test_case("#..\n.#\n", vec![Column(2)])]
+    #[test_case("", vec![])]
+    fn test_empty_detection(lines: &str, expand_list: Vec<Expand>) {
+        let universe = Universe::new(lines.to_owned(), 2);
+        assert_eq!(universe.expand_list(), expand_list)
+    }
+
+    #[test]
+    fn test_expansion_per_axis() {
+        let lines = "..\n.#";
+        let galaxies = |columns, rows| Universe::with_expansion(lines, Expansion { columns, rows }).galaxies;
+        assert_eq!(galaxies(1, 1), vec![Galaxy {x: 1, y: 1}]);
+        assert_eq!(galaxies(10, 1), vec![Galaxy {x: 10, y: 1}]);
+        assert_eq!(galaxies(1, 5), vec![Galaxy {x: 1, y: 5}]);
+        // empty space can also collapse
+        assert_eq!(galaxies(0, 0), vec![Galaxy {x: 0, y: 0}]);
     }
 
     #[test_case("..\n..", vec![])]
@@ -154,4 +335,66 @@ mod test {
         let a = vec![1, 2, 3, 4];
         assert_eq!(pairs(a), vec![(1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)])
     }
+
+    #[test_case(2, 374)]
+    #[test_case(10, 1030)]
+    #[test_case(100, 8410)]
+    #[test_case(1_000_000_000_000, 82_000_000_000_210)]
+    fn test_distance_sum(expansion_factor: usize, sum: u128) {
+        let universe = Universe::new(EXAMPLE.to_owned(), expansion_factor);
+        assert_eq!(distance_sum(&universe.galaxies), sum);
+        let brute_force: usize = pairs(universe.galaxies).iter().map(|(a, b)| a.cost(b)).sum();
+        assert_eq!(brute_force as u128, sum);
+    }
+
+    #[test]
+    fn test_distance_sum_u128() {
+        // far apart galaxies whose distances don't fit in a u64 once summed
+        let far = usize::MAX / 2;
+        let galaxies = vec![Galaxy {x: 0, y: 0}, Galaxy {x: far, y: 0}, Galaxy {x: 0, y: far}, Galaxy {x: far, y: far}];
+        assert_eq!(distance_sum(&galaxies), 8 * far as u128);
+    }
+
+    #[test_case(Metric::Manhattan, 5.0)]
+    #[test_case(Metric::Chebyshev, 3.0)]
+    #[test_case(Metric::Euclidean, 13f64.sqrt())]
+    fn test_metric(metric: Metric, distance: f64) {
+        assert_eq!(metric.distance(&Galaxy {x: 0, y: 0}, &Galaxy {x: 2, y: 3}), distance);
+    }
+
+    #[test]
+    fn test_nearest_farthest() {
+        let universe = Universe::new(EXAMPLE.to_owned(), 2);
+        for metric in [Metric::Manhattan, Metric::Chebyshev, Metric::Euclidean] {
+            for i in 0..universe.galaxies.len() {
+                let key = |j: usize| metric.key(&universe.galaxies[i], &universe.galaxies[j]);
+                let others: Vec<usize> = (0..universe.galaxies.len()).filter(|j| *j != i).collect();
+                let nearest = universe.nearest(i, metric).unwrap();
+                let farthest = universe.farthest(i, metric).unwrap();
+                assert!(others.iter().all(|j| key(nearest) <= key(*j) && key(farthest) >= key(*j)));
+            }
+        }
+        assert_eq!(Universe::new("#".to_owned(), 2).nearest(0, Metric::Manhattan), None);
+    }
+
+    #[test_case(Metric::Manhattan)]
+    #[test_case(Metric::Chebyshev)]
+    #[test_case(Metric::Euclidean)]
+    fn test_closest_pairs(metric: Metric) {
+        let universe = Universe::new(EXAMPLE.to_owned(), 2);
+        let g = &universe.galaxies;
+        let mut all: Vec<(usize, usize)> = pairs((0..g.len()).collect());
+        all.sort_by_key(|(i, j)| (metric.key(&g[*i], &g[*j]), *i, *j));
+        assert_eq!(universe.closest_pairs(5, metric), all[..5]);
+        assert_eq!(universe.closest_pairs(100, metric), all);
+        assert_eq!(universe.closest_pairs(0, metric), vec![]);
+    }
+
+    #[test]
+    fn test_distance_matrix() {
+        let universe = Universe::new("#..#\n....\n....\n....\n#...".to_owned(), 1);
+        assert_eq!(universe.distance_matrix(Metric::Manhattan), "0,3,4\n3,0,7\n4,7,0\n");
+        assert_eq!(universe.distance_matrix(Metric::Chebyshev), "0,3,4\n3,0,4\n4,4,0\n");
+        assert_eq!(universe.distance_matrix(Metric::Euclidean), "0,3,4\n3,0,5\n4,5,0\n");
+    }
 }
@@ -1,6 +1,4 @@
-use std::{vec, fmt::Debug, fs};
-
-use itertools::Itertools;
+use std::{collections::HashMap, env, fs};
 
 struct A {
     line: String,
@@ -17,7 +15,7 @@ impl A {
 
     fn new2(line: String) -> Self {
         let (code, group_str) = line.split_once(' ').unwrap();
-        let mut groups: Vec<usize> = group_str.split(',').map(|i| i.parse().unwrap()).collect();
+        let groups: Vec<usize> = group_str.split(',').map(|i| i.parse().unwrap()).collect();
         let copied_code = format!("{0}?{0}?{0}?{0}?{0}", code);
         let mut copied_groups = groups.clone();
         copied_groups.append(&mut groups.clone());
@@ -28,146 +26,231 @@ impl A {
         Self { line: copied_code, continues_groups: copied_groups }
     }
 
-    // brute force:
-    // create all different possible combinations of replacing '?'
-    // count valid
-    fn combinations(&self) -> usize {
-        let total_count: usize = self.continues_groups.iter().sum();
-        let counts = self.line.chars().counts();
-        let hash_count = counts.get(&'#').unwrap_or(&0);
-        let unknown_points: Vec<usize> = self.line.chars().enumerate().filter_map(|(i, c)| {
-            if c == '?' {
-                Some(i)
-            } else {
-                None
-            }
-        }).collect();
-        let replace_count = total_count - hash_count;
-        let combinations = groups(&unknown_points, replace_count);
-
-        combinations.into_iter().map(|combination| {
-            let mut line = self.line.clone();
-            for index in combination {
-                line.replace_range(index..=index, "#");
+    // arrangements of the '?' that give exactly the groups. Walking the line
+    // only the position, the group being filled and how much of it is already
+    // filled matter, so the count is remembered for every such state
+    fn arrangements(&self) -> u128 {
+        let line = self.line.as_bytes();
+        let mut memo = HashMap::new();
+        self.count(line, 0, 0, 0, &mut memo)
+    }
+
+    fn count(&self, line: &[u8], position: usize, group: usize, run: usize, memo: &mut HashMap<(usize, usize, usize), u128>) -> u128 {
+        let groups = &self.continues_groups;
+        if position == line.len() {
+            let done = match run {
+                0 => group == groups.len(),
+                _ => group + 1 == groups.len() && groups[group] == run,
+            };
+            return done as u128;
+        }
+        if let Some(count) = memo.get(&(position, group, run)) {
+            return *count;
+        }
+
+        let mut total = 0;
+        // '.' ends the current group, which has to be complete by then
+        if line[position] != b'#' {
+            if run == 0 {
+                total += self.count(line, position + 1, group, 0, memo);
+            } else if groups[group] == run {
+                total += self.count(line, position + 1, group + 1, 0, memo);
             }
-            let line = line.replace("?", ".");
-            let splits = line.split(".").filter(|s| !s.is_empty()).map(|s| s.len()).collect_vec();
-            if splits.eq(&self.continues_groups) {
-                1
+        }
+        // '#' grows the current group, as long as it isn't full yet
+        if line[position] != b'.' && group < groups.len() && run < groups[group] {
+            total += self.count(line, position + 1, group, run + 1, memo);
+        }
+
+        memo.insert((position, group, run), total);
+        total
+    }
+
+    // every arrangement as a concrete line, in lexicographic order
+    fn iter(&self) -> Arrangements<'_> {
+        Arrangements { record: self, memo: HashMap::new(), index: 0, total: self.arrangements() }
+    }
+}
+
+// arrangements are numbered in lexicographic order and built one at a time,
+// so jumping ahead with `nth` or `skip` doesn't build the ones in between
+struct Arrangements<'a> {
+    record: &'a A,
+    memo: HashMap<(usize, usize, usize), u128>,
+    index: u128,
+    total: u128,
+}
+
+impl Arrangements<'_> {
+    // the arrangement numbered `index`. '#' sorts before '.', so at every '?'
+    // the first count(after '#') arrangements put a '#' there
+    fn arrangement(&mut self, mut index: u128) -> String {
+        let line = self.record.line.as_bytes();
+        let groups = &self.record.continues_groups;
+        let (mut group, mut run) = (0, 0);
+
+        let mut arrangement = String::with_capacity(line.len());
+        for position in 0..line.len() {
+            let spring = line[position] != b'.' && group < groups.len() && run < groups[group];
+            let with_spring = match spring {
+                true => self.record.count(line, position + 1, group, run + 1, &mut self.memo),
+                false => 0,
+            };
+            if index < with_spring {
+                arrangement.push('#');
+                run += 1;
             } else {
-                0
+                index -= with_spring;
+                arrangement.push('.');
+                if run > 0 {
+                    group += 1;
+                    run = 0;
+                }
             }
-        }).sum::<usize>().max(1)
+        }
+        arrangement
     }
-
 }
 
-fn groups<T>(v: &Vec<T>, size: usize) -> Vec<Vec<T>> 
-where 
-    T: Copy + Clone + Debug,
-{
-    if size == 1 {
-        return v.iter().map(|&e| vec![e]).collect();
-    } else if size == 0 {
-        return vec![];
-    }
-    v.iter().enumerate().map(|(i, e)| {
-        let groups = groups(v[i+1..v.len()].to_vec().as_ref(), size - 1);
-        groups.into_iter().map(|mut group| {
-            group.push(*e);
-            group
-        }).collect_vec()
-    }).flatten().collect()
+impl Iterator for Arrangements<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.index >= self.total {
+            return None;
+        }
+        let arrangement = self.arrangement(self.index);
+        self.index += 1;
+        Some(arrangement)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<String> {
+        self.index = self.index.saturating_add(n as u128).min(self.total);
+        self.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = usize::try_from(self.total - self.index);
+        (left.unwrap_or(usize::MAX), left.ok())
+    }
 }
 
-fn part1() -> usize {
+fn part1() -> u128 {
     let file = fs::read_to_string("src/23/day12/input.txt").unwrap();
-    let lines = file.lines().collect_vec();
-    lines.into_iter().map(|line| {
-        let a = A::new(line.to_owned());
-        let count = a.combinations();
-        dbg!(&line, count);
-        count
-    }).sum()
+    file.lines().map(|line| A::new(line.to_owned()).arrangements()).sum()
 }
 
-// TODO need to optimize, takes to long to run
-//
-// instead of doing brute force we need to narrow it down from the edges
-// like:
-// ?#?#?#?#?#?#?#? 1,3,1,6
-// > .#.#?#?#?#?#?#? 3,1,6
-// > .#.###.#?#?#?#? 1,6
-// > .#.###.#.#?#?#? 6
-// > .#.###.#.###### 6
-// = 1
-//
-// 1. find first cluster (chars closed of with '.'/'?' or have max '#' == to current group)
-// 2. for each new possible string continue with next group
-// 3.1   ('?#', 1) brute force find possible strings
-//   > .#.#?#?#?#?#?#? 3,1,6
-// 3.2   ('#?#?#', 3) brute force find possible strings
-//   > .#.###.#?#?#?#? 1,6
-// 3.3   ('#', 1) brute force find possible strings
-//   > .#.###.#.#?#?#? 6
-// 3.4   ('#?#?#?', 6) brute force find possible strings
-//   > .#.###.#.######
-// 4. sum counts
-//   > 1
-fn part2() -> usize {
+fn part2() -> u128 {
     let file = fs::read_to_string("src/23/day12/input.txt").unwrap();
-    let lines = file.lines().collect_vec();
-    lines.into_iter().map(|line| {
-        let a = A::new2(line.to_owned());
-        let count = a.combinations();
-        dbg!(&line, count);
-        count
-    }).sum()
+    file.lines().map(|line| A::new2(line.to_owned()).arrangements()).sum()
 }
 
-
 fn main() {
+    // `23-day12 "<record>" <skip> <take>` lists arrangements of one record
+    let args: Vec<String> = env::args().collect();
+    if let [_, record, skip, take] = args.as_slice() {
+        let a = A::new(record.to_owned());
+        println!("{} arrangements", a.arrangements());
+        for arrangement in a.iter().skip(skip.parse().unwrap()).take(take.parse().unwrap()) {
+            println!("{}", arrangement);
+        }
+        return;
+    }
+
     println!("p1: {}", part1());
     println!("p2: {}", part2());
 }
 
 #[cfg(test)]
 mod test {
-    use std::vec;
-
+    use itertools::Itertools;
     use test_case::test_case;
 
     use crate::A;
-    use crate::groups;
+
+    // brute force:
+    // try every way of replacing the '?' and keep the valid ones
+    fn brute_force(a: &A) -> Vec<String> {
+        let unknown = a.line.chars().filter(|c| *c == '?').count();
+        let mut valid = (0..1u32 << unknown).map(|mask| {
+            let mut bit = 0;
+            a.line.chars().map(|c| match c {
+                '?' => {
+                    bit += 1;
+                    if mask & (1 << (bit - 1)) != 0 { '#' } else { '.' }
+                }
+                c => c,
+            }).collect::<String>()
+        }).filter(|line| {
+            let splits = line.split('.').filter(|s| !s.is_empty()).map(|s| s.len()).collect_vec();
+            splits.eq(&a.continues_groups)
+        }).collect_vec();
+        valid.sort();
+        valid
+    }
 
     #[test_case(".???#?.??? 1,3,3", 1)]
     #[test_case(".????#?.??? 1,3,3", 3)]
     #[test_case("??.??#??##.?.?# 2,3,2,1,2", 2)]
     #[test_case("?#???#.?##? 1,1,2", 1)]
+    #[test_case("???.### 1,1,3", 1)]
+    #[test_case("?###???????? 3,2,1", 10)]
+    #[test_case("#.# 1,1", 1)]
+    #[test_case("### 1,1", 0)]
+    #[test_case("??? 4", 0)]
     fn test(line: &str, count: usize) {
         let a = A::new(line.to_owned());
         //assert_eq!(a.continues_groups, vec![1, 3, 3]);
-        assert_eq!(a.combinations(), count);
+        assert_eq!(brute_force(&a).len(), count);
+        assert_eq!(a.arrangements(), count as u128);
+        assert_eq!(a.iter().collect_vec(), brute_force(&a));
     }
 
     #[test_case("????.#...#... 4,1,1", 16)]
     #[test_case("????.######..#####. 1,6,5", 2500)]
     #[test_case(".??..??...?##. 1,1,3", 16384)]
-    fn test2(line: &str, count: usize) {
+    #[test_case("?###???????? 3,2,1", 506250)]
+    fn test2(line: &str, count: u128) {
         let a = A::new2(line.to_owned());
         //assert_eq!(a.continues_groups, vec![1, 3, 3]);
-        assert_eq!(a.combinations(), count);
+        assert_eq!(a.arrangements(), count);
     }
 
     #[test]
-    fn test_groups() {
+    fn test_large() {
+        // 200 '?' fit 40 groups of 1 in C(161, 40) ways, more than a u64 holds
+        let a = A::new(format!("{} {}", "?".repeat(200), vec!["1"; 40].join(",")));
+        assert_eq!(a.arrangements(), 114_936_463_738_633_850_439_458_482_880_400_722_800);
+    }
 
-        let a = vec![1, 2];
-        assert_eq!(groups(&a, 1), vec![vec![1], vec![2]]);
-        let a = vec![1, 2, 3];
-        assert_eq!(groups(&a, 2), vec![vec![2, 1], vec![3, 1], vec![3, 2]]);
-        let a = vec![1, 2, 3, 4];
-        assert_eq!(groups(&a, 3), vec![vec![3, 2, 1], vec![4, 2, 1], vec![4, 3, 1], vec![4, 3, 2]]);
+    #[test]
+    fn test_iter() {
+        let a = A::new("?###???????? 3,2,1".to_owned());
+        let all = a.iter().collect_vec();
+        assert_eq!(all[0], ".###.##.#...");
+        assert_eq!(all[9], ".###....##.#");
+        for n in 0..12 {
+            assert_eq!(a.iter().nth(n), all.get(n).cloned());
+            assert_eq!(a.iter().skip(n).collect_vec(), all[n.min(10)..]);
+        }
+        let mut iter = a.iter();
+        assert_eq!(iter.size_hint(), (10, Some(10)));
+        iter.nth(3);
+        assert_eq!(iter.size_hint(), (6, Some(6)));
     }
 
+    #[test]
+    fn test_iter_large() {
+        // far too many to list, but jumping around is quick
+        let a = A::new2("?###???????? 3,2,1".to_owned());
+        let sampled = [0, 1, 1000, 250000, 506248, 506249].map(|n| a.iter().nth(n).unwrap());
+        for arrangement in &sampled {
+            let splits = arrangement.split('.').filter(|s| !s.is_empty()).map(|s| s.len()).collect_vec();
+            assert_eq!(splits, a.continues_groups);
+        }
+        assert!(sampled.windows(2).all(|w| w[0] < w[1]));
+        let mut iter = a.iter();
+        assert!(iter.nth(506249).is_some());
+        assert_eq!(iter.next(), None);
+    }
 }
@@ -1,6 +1,6 @@
-use std::{fs, ops::RangeBounds, num};
+use std::{collections::BTreeMap, fs};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Number {
     column: usize,
     row: usize,
@@ -11,159 +11,222 @@ impl Number {
     fn len(&self) -> usize {
         (self.value.checked_ilog10().unwrap_or(0) + 1) as usize
     }
+
+    // symbol is in the box one tile around the number
+    fn touches(&self, symbol: &Symbol) -> bool {
+        symbol.row.abs_diff(self.row) <= 1
+            && symbol.column + 1 >= self.column
+            && symbol.column <= self.column + self.len()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Symbol {
     column: usize,
     row: usize,
+    c: char,
 }
 
-fn solve1() -> u32 {
-    let mut numbers: Vec<Number> = Vec::new();
-    let mut symbols: Vec<Symbol> = Vec::new();
-
-    let file_string = fs::read_to_string("src/23/day3/input.txt").expect("error");
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GearCount {
+    Exactly(usize),
+    AtLeast(usize),
+}
 
-    let mut current_number = None;
-    for (row, line) in file_string.lines().enumerate() {
-        for (column, char) in line.chars().enumerate() {
-            match char {
-                '0'..='9' => match current_number {
-                    None => current_number = Some(char.to_digit(10).unwrap()),
-                    Some(number) => {
-                        current_number = Some(number * 10 + char.to_digit(10).unwrap());
-                        if column == 139 {
-                            if let Some(number) = current_number {
-                                dbg!(column, number.ilog10());
-                                numbers.push(Number {
-                                    column: column - number.ilog10() as usize,
-                                    row: row + 1,
-                                    value: number,
-                                });
-                                current_number = None;
-                            }
-                        }
-                    }
-                },
-                '.' => {
-                    if let Some(number) = current_number {
-                        dbg!(column, number.ilog10());
-                        numbers.push(Number {
-                            column: column - number.ilog10() as usize,
-                            row: row + 1,
-                            value: number,
-                        });
-                        current_number = None;
-                    }
-                }
-                _ => {
-                    if let Some(number) = current_number {
-                        numbers.push(Number {
-                            column: column - number.ilog10() as usize,
-                            row: row + 1,
-                            value: number,
-                        });
-                        current_number = None;
-                    }
-                    symbols.push(Symbol { column: column + 1, row: row + 1 });
-                }
-            }
+impl GearCount {
+    fn matches(&self, count: usize) -> bool {
+        match self {
+            GearCount::Exactly(n) => count == *n,
+            GearCount::AtLeast(n) => count >= *n,
         }
     }
-    dbg!(&numbers);
+}
 
-    return numbers.iter()
-        .map(|number| {
-            let x_range = number.row-1..=number.row+1;
-            for symbol in &symbols {
-                if x_range.contains(&symbol.row) {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Aggregate {
+    Product,
+    Sum,
+}
 
-                    if (number.column-1..=number.column + number.len()).contains(&symbol.column) {
-                        dbg!("x & y", &number, symbol, &x_range);
-                        return number.value;
-                    }
-                }
-            }
-            0
-        }).sum::<u32>();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct GearRule {
+    symbol: char,
+    count: GearCount,
+    aggregate: Aggregate,
+}
+
+impl Default for GearRule {
+    fn default() -> Self {
+        GearRule {
+            symbol: '*',
+            count: GearCount::Exactly(2),
+            aggregate: Aggregate::Product,
+        }
+    }
 }
 
-fn solve2() -> u32 {
-    let mut numbers: Vec<Number> = Vec::new();
-    let mut symbols: Vec<Symbol> = Vec::new();
+struct Schematic {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+}
 
-    let file_string = fs::read_to_string("src/23/day3/input.txt").expect("error");
+impl Schematic {
+    fn new(content: &str) -> Self {
+        let mut numbers: Vec<Number> = Vec::new();
+        let mut symbols: Vec<Symbol> = Vec::new();
 
-    let mut current_number = None;
-    for (row, line) in file_string.lines().enumerate() {
-        for (column, char) in line.chars().enumerate() {
-            match char {
-                '0'..='9' => match current_number {
-                    None => current_number = Some(char.to_digit(10).unwrap()),
-                    Some(number) => {
-                        current_number = Some(number * 10 + char.to_digit(10).unwrap());
-                        if column == 139 {
-                            if let Some(number) = current_number {
-                                dbg!(column, number.ilog10());
-                                numbers.push(Number {
-                                    column: column - number.ilog10() as usize,
-                                    row: row + 1,
-                                    value: number,
-                                });
-                                current_number = None;
-                            }
-                        }
-                    }
-                },
-                '.' => {
-                    if let Some(number) = current_number {
-                        dbg!(column, number.ilog10());
-                        numbers.push(Number {
-                            column: column - number.ilog10() as usize,
-                            row: row + 1,
-                            value: number,
-                        });
-                        current_number = None;
-                    }
+        for (row, line) in content.lines().enumerate() {
+            let mut current_number: Option<(usize, u32)> = None;
+            for (column, c) in line.chars().enumerate() {
+                if let Some(digit) = c.to_digit(10) {
+                    current_number = match current_number {
+                        None => Some((column, digit)),
+                        Some((start, number)) => Some((start, number * 10 + digit)),
+                    };
+                    continue;
                 }
-                _ => {
-                    if let Some(number) = current_number {
-                        numbers.push(Number {
-                            column: column - number.ilog10() as usize,
-                            row: row + 1,
-                            value: number,
-                        });
-                        current_number = None;
-                    }
-                    symbols.push(Symbol { column: column + 1, row: row + 1 });
+
+                if let Some((start, value)) = current_number.take() {
+                    numbers.push(Number { column: start, row, value });
+                }
+                if c != '.' {
+                    symbols.push(Symbol { column, row, c });
                 }
             }
+            // number at the end of the line
+            if let Some((start, value)) = current_number {
+                numbers.push(Number { column: start, row, value });
+            }
         }
+
+        Schematic { numbers, symbols }
     }
-    dbg!(&numbers);
 
-    return symbols.iter()
-        .map(|symbol| {
-            let x_range = symbol.row-1..=symbol.row+1;
-            let mut count = Vec::new();
-            for number in &numbers {
-                if x_range.contains(&number.row) {
+    fn adjacent(&self, symbol: &Symbol) -> Vec<&Number> {
+        self.numbers.iter().filter(|n| n.touches(symbol)).collect()
+    }
 
-                    if (number.column-1..=number.column + number.len()).contains(&symbol.column) {
-                        dbg!("x & y", &number, symbol, &x_range);
-                        count.push(number.value);
-                    }
-                }
-            }
-            if count.len() == 2 {
-                return count.into_iter().reduce(|a, b| a * b).unwrap();
+    fn is_part(&self, number: &Number) -> bool {
+        self.symbols.iter().any(|s| number.touches(s))
+    }
+
+    fn part_sum(&self) -> u32 {
+        self.numbers.iter().filter(|n| self.is_part(n)).map(|n| n.value).sum()
+    }
+
+    // a number touching several kinds of symbols is counted for each of them
+    fn part_sums(&self) -> BTreeMap<char, u32> {
+        let mut sums = BTreeMap::new();
+        for number in &self.numbers {
+            let mut kinds: Vec<char> = self
+                .symbols
+                .iter()
+                .filter(|s| number.touches(s))
+                .map(|s| s.c)
+                .collect();
+            kinds.sort();
+            kinds.dedup();
+            for c in kinds {
+                *sums.entry(c).or_insert(0) += number.value;
             }
-            0
-        }).sum::<u32>();
+        }
+        sums
+    }
+
+    fn orphans(&self) -> Vec<&Number> {
+        self.numbers.iter().filter(|n| !self.is_part(n)).collect()
+    }
+
+    fn gear_ratios(&self, rule: &GearRule) -> Vec<u64> {
+        self.symbols
+            .iter()
+            .filter(|s| s.c == rule.symbol)
+            .filter_map(|s| {
+                let parts = self.adjacent(s);
+                if !rule.count.matches(parts.len()) {
+                    return None;
+                }
+                let values = parts.iter().map(|n| n.value as u64);
+                Some(match rule.aggregate {
+                    Aggregate::Product => values.product(),
+                    Aggregate::Sum => values.sum(),
+                })
+            })
+            .collect()
+    }
+}
+
+fn solve1() -> u32 {
+    let file_string = fs::read_to_string("src/23/day3/input.txt").expect("error");
+    Schematic::new(&file_string).part_sum()
+}
+
+fn solve2() -> u64 {
+    let file_string = fs::read_to_string("src/23/day3/input.txt").expect("error");
+    Schematic::new(&file_string)
+        .gear_ratios(&GearRule::default())
+        .iter()
+        .sum()
 }
 
 fn main() {
     println!("p1: {}", solve1());
     println!("p2: {}", solve2());
+
+    let file_string = fs::read_to_string("src/23/day3/input.txt").expect("error");
+    let schematic = Schematic::new(&file_string);
+    for (symbol, sum) in schematic.part_sums() {
+        println!("parts '{}': {}", symbol, sum);
+    }
+    let orphans: Vec<u32> = schematic.orphans().iter().map(|n| n.value).collect();
+    println!("orphans: {:?}", orphans);
+
+    let crowded = GearRule {
+        count: GearCount::AtLeast(3),
+        aggregate: Aggregate::Sum,
+        ..GearRule::default()
+    };
+    println!("gears with 3+ parts: {:?}", schematic.gear_ratios(&crowded));
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use crate::{Aggregate, GearCount, GearRule, Schematic};
+
+    const EXAMPLE: &str = "467..114..\n...*......\n..35..633.\n......#...\n617*......\n.....+.58.\n..592.....\n......755.\n...$.*....\n.664.598..";
+
+    #[test]
+    fn test_parts() {
+        let schematic = Schematic::new(EXAMPLE);
+        assert_eq!(schematic.part_sum(), 4361);
+        let orphans: Vec<u32> = schematic.orphans().iter().map(|n| n.value).collect();
+        assert_eq!(orphans, vec![114, 58]);
+    }
+
+    #[test]
+    fn test_part_sums() {
+        let sums = Schematic::new(EXAMPLE).part_sums();
+        assert_eq!(sums.get(&'*'), Some(&(467 + 35 + 617 + 755 + 598)));
+        assert_eq!(sums.get(&'#'), Some(&(633)));
+        assert_eq!(sums.get(&'$'), Some(&(664)));
+        assert_eq!(sums.get(&'+'), Some(&(592)));
+    }
+
+    #[test]
+    fn test_number_at_line_end() {
+        let schematic = Schematic::new("..12\n.*..\n..34");
+        assert_eq!(schematic.part_sum(), 46);
+    }
+
+    #[test_case('*', GearCount::Exactly(2), Aggregate::Product, 467835)]
+    #[test_case('*', GearCount::Exactly(2), Aggregate::Sum, 467 + 35 + 755 + 598)]
+    #[test_case('*', GearCount::AtLeast(1), Aggregate::Sum, 467 + 35 + 617 + 755 + 598)]
+    #[test_case('#', GearCount::Exactly(1), Aggregate::Product, 633)]
+    fn test_gear_ratios(symbol: char, count: GearCount, aggregate: Aggregate, total: u64) {
+        let rule = GearRule { symbol, count, aggregate };
+        let ratios = Schematic::new(EXAMPLE).gear_ratios(&rule);
+        assert_eq!(ratios.iter().sum::<u64>(), total);
+    }
 }
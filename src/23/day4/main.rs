@@ -1,95 +1,204 @@
-use std::fs;
+use std::{collections::HashSet, fmt, fs, num::ParseIntError, str::FromStr};
 
+use num::BigUint;
+
+#[derive(Debug)]
 struct Card {
     number: u32,
-    winning_numbers: Vec<u32>,
+    winning_numbers: HashSet<u32>,
     hand_numbers: Vec<u32>,
 }
 
-#[derive(Debug, Clone, Copy)]
-struct ScratchCard {
-    number: u32,
+#[derive(Debug, PartialEq, Eq)]
+enum CardError {
+    Format(String),
+    Number(ParseIntError),
+    Duplicate { card: u32, number: u32 },
+    NonContiguous { expected: u32, found: u32 },
 }
 
-impl Card {
-    fn points(&self) -> u32 {
-        match self.matches() {
-            0 => return 0,
-            n => {
-                let mut total = 1;
-                for _ in 1..n {
-                    total = total * 2;
-                }
-                return total;
+impl fmt::Display for CardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CardError::Format(line) => write!(f, "malformed card: {:?}", line),
+            CardError::Number(e) => write!(f, "invalid number: {}", e),
+            CardError::Duplicate { card, number } => {
+                write!(f, "card {} lists {} more than once", card, number)
+            }
+            CardError::NonContiguous { expected, found } => {
+                write!(f, "expected card {} but found card {}", expected, found)
             }
         }
     }
+}
 
-    fn matches(&self) -> u32 {
-        self.hand_numbers.iter().filter(|i| self.winning_numbers.contains(i)).count() as u32
+impl From<ParseIntError> for CardError {
+    fn from(e: ParseIntError) -> Self {
+        CardError::Number(e)
     }
+}
+
+// "Card   1: 41 48 83 | 83 86  6"
+impl FromStr for Card {
+    type Err = CardError;
 
-    fn get_cards(&self) -> Vec<ScratchCard> {
-        let mut cards: Vec<ScratchCard> = Vec::new();
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let format_error = || CardError::Format(line.to_owned());
+        let (card, numbers) = line.split_once(':').ok_or_else(format_error)?;
+        let (win_nums, hand_nums) = numbers.split_once('|').ok_or_else(format_error)?;
+        let number: u32 = card
+            .strip_prefix("Card")
+            .ok_or_else(format_error)?
+            .trim()
+            .parse()?;
+
+        let mut winning_numbers = HashSet::new();
+        for n in win_nums.split_whitespace() {
+            let n: u32 = n.parse()?;
+            if !winning_numbers.insert(n) {
+                return Err(CardError::Duplicate { card: number, number: n });
+            }
+        }
 
-        let index = self.number;
-        for i in 1..=self.matches() {
-            cards.push(ScratchCard { number: i + index })
+        let mut seen = HashSet::new();
+        let mut hand_numbers = Vec::new();
+        for n in hand_nums.split_whitespace() {
+            let n: u32 = n.parse()?;
+            if !seen.insert(n) {
+                return Err(CardError::Duplicate { card: number, number: n });
+            }
+            hand_numbers.push(n);
         }
-        cards
+
+        Ok(Card { number, winning_numbers, hand_numbers })
     }
 }
 
+// cards have to be numbered 1, 2, 3, ... for the copies to cascade correctly
+fn parse_cards(content: &str) -> Result<Vec<Card>, CardError> {
+    let mut cards = Vec::new();
+    for (i, line) in content.lines().filter(|l| !l.trim().is_empty()).enumerate() {
+        let card: Card = line.parse()?;
+        let expected = i as u32 + 1;
+        if card.number != expected {
+            return Err(CardError::NonContiguous { expected, found: card.number });
+        }
+        cards.push(card);
+    }
+    Ok(cards)
+}
 
-fn part1() -> u32 {
-    return fs::read_to_string("src/23/day4/input.txt").expect("error")
-        .lines()
-        .map(|l| {
-            let (win, hand_nums) = l.split_once('|').unwrap();
-            let (card, win_nums) = win.split_once(':').unwrap();
-            let (_, card_number) = card.split_once(' ').unwrap();
+impl Card {
+    // doubles for every match after the first, a BigUint so large cards
+    // with hundreds of matches still score
+    fn points(&self) -> BigUint {
+        match self.matches() {
+            0 => BigUint::from(0u32),
+            n => BigUint::from(1u32) << (n - 1),
+        }
+    }
 
-            Card {
-                number: card_number.trim().parse().unwrap(),
-                winning_numbers: win_nums.split_whitespace().map(|s| s.parse().unwrap()).collect(),
-                hand_numbers: hand_nums.split_whitespace().map(|s| s.parse().unwrap()).collect(),
-            }
-        }).map(|c| c.points()).sum();
+    fn matches(&self) -> u32 {
+        self.hand_numbers.iter().filter(|i| self.winning_numbers.contains(i)).count() as u32
+    }
 }
 
-fn part2() -> u32 {
-    let cards: Vec<Card> = fs::read_to_string("src/23/day4/input.txt").expect("error")
-        .lines()
-        .map(|l| {
-            let (win, hand_nums) = l.split_once('|').unwrap();
-            let (card, win_nums) = win.split_once(':').unwrap();
-            let (_, card_number) = card.split_once(' ').unwrap();
-
-            Card {
-                number: card_number.trim().parse().unwrap(),
-                winning_numbers: win_nums.split_whitespace().map(|s| s.parse().unwrap()).collect(),
-                hand_numbers: hand_nums.split_whitespace().map(|s| s.parse().unwrap()).collect(),
-            }
-        }).collect();
-
-    let mut scratch_cards: Vec<ScratchCard> = cards.iter().map(|c| ScratchCard { number: c.number }).collect();
-    
-    cards.iter().for_each(|c| {
-        let num_scratch_cards = scratch_cards.iter().filter(|s| s.number == c.number).count();
-        if num_scratch_cards == 0 {
-            return;
-        }
+// count copies per card instead of materialising every won card, each card
+// hands its copies to the `matches` cards after it (never past the last card)
+fn total_cards(cards: &[Card]) -> u128 {
+    let mut copies: Vec<u128> = vec![1; cards.len()];
 
-        let win_cards = c.get_cards();
-        for _ in 0..num_scratch_cards {
-            scratch_cards.extend(&win_cards);
+    for (i, card) in cards.iter().enumerate() {
+        let won = card.matches() as usize;
+        let end = (i + 1 + won).min(cards.len());
+        for j in i + 1..end {
+            copies[j] += copies[i];
         }
-    });
-    scratch_cards.len() as u32
+    }
+    copies.iter().sum()
+}
+
+
+fn part1() -> BigUint {
+    let file = fs::read_to_string("src/23/day4/input.txt").expect("error");
+    parse_cards(&file).unwrap().iter().map(|c| c.points()).sum()
+}
+
+fn part2() -> u128 {
+    let file = fs::read_to_string("src/23/day4/input.txt").expect("error");
+    total_cards(&parse_cards(&file).unwrap())
 }
 
 
 fn main() {
     println!("p1: {}", part1());
-    println!("p1: {}", part2());
+    println!("p2: {}", part2());
+}
+
+#[cfg(test)]
+mod test {
+    use num::BigUint;
+    use test_case::test_case;
+
+    use crate::{parse_cards, total_cards, Card, CardError};
+
+    fn card(number: u32, matches: u32) -> Card {
+        Card {
+            number,
+            winning_numbers: (1..=matches).collect(),
+            hand_numbers: (1..=matches).collect(),
+        }
+    }
+
+    #[test]
+    fn test_total_cards() {
+        let cards: Vec<Card> = [4, 2, 2, 1, 0, 0]
+            .into_iter()
+            .enumerate()
+            .map(|(i, m)| card(i as u32 + 1, m))
+            .collect();
+        assert_eq!(total_cards(&cards), 30);
+    }
+
+    #[test]
+    fn test_total_cards_deep_cascade() {
+        let cards: Vec<Card> = (1..=100).map(|i| card(i, 2)).collect();
+        assert_eq!(total_cards(&cards), 2427893228399975082350);
+    }
+
+    #[test]
+    fn test_parse() {
+        let card: Card = "Card   1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53".parse().unwrap();
+        assert_eq!(card.number, 1);
+        assert_eq!(card.winning_numbers.len(), 5);
+        assert_eq!(card.hand_numbers, vec![83, 86, 6, 31, 17, 9, 48, 53]);
+        assert_eq!(card.matches(), 4);
+        assert_eq!(card.points(), BigUint::from(8u32));
+    }
+
+    #[test_case(0, 0u32.into())]
+    #[test_case(1, 1u32.into())]
+    #[test_case(32, (1u64 << 31).into())]
+    #[test_case(33, (1u64 << 32).into())]
+    #[test_case(200, BigUint::from(1u32) << 199)]
+    fn test_points(matches: u32, points: BigUint) {
+        assert_eq!(card(1, matches).points(), points);
+    }
+
+    #[test_case("Card 1: 41 41 | 83", CardError::Duplicate { card: 1, number: 41 })]
+    #[test_case("Card 1: 41 | 83 6 83", CardError::Duplicate { card: 1, number: 83 })]
+    #[test_case("Card 1: 41 83", CardError::Format("Card 1: 41 83".to_owned()))]
+    #[test_case("Cart 1: 41 | 83", CardError::Format("Cart 1: 41 | 83".to_owned()))]
+    fn test_parse_error(line: &str, error: CardError) {
+        assert_eq!(line.parse::<Card>().unwrap_err(), error);
+    }
+
+    #[test]
+    fn test_parse_cards_non_contiguous() {
+        let cards = "Card 1: 1 | 1\nCard 3: 1 | 1";
+        assert_eq!(
+            parse_cards(cards).unwrap_err(),
+            CardError::NonContiguous { expected: 2, found: 3 }
+        );
+    }
 }
@@ -1,11 +1,7 @@
-use std::fs;
+use std::{fmt, fs, ops::Range};
 
 
-struct Seed {
-    number: u64,
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Converter {
     source: u64,
     dest: u64,
@@ -14,17 +10,52 @@ struct Converter {
 
 impl Converter {
     fn convert(&self, value: u64) -> Option<u64> {
-        if !(self.source..self.source+self.range).contains(&value) {
+        if !self.source_range().contains(&value) {
             return None;
         }
-        let diff: i64 = self.dest as i64 - self.source as i64;
-        Some((value as i64 + diff) as u64)
+        Some(value - self.source + self.dest)
+    }
+
+    fn dest_range(&self) -> Range<u64> {
+        self.dest..self.dest + self.range
+    }
+
+    fn source_range(&self) -> Range<u64> {
+        self.source..self.source + self.range
+    }
+
+    // splits `range` into the part this converter maps (already converted)
+    // and the parts before and after it which are left untouched
+    fn convert_range(&self, range: &Range<u64>) -> (Option<Range<u64>>, Vec<Range<u64>>) {
+        let source = self.source_range();
+        let start = range.start.max(source.start);
+        let end = range.end.min(source.end);
+        if start >= end {
+            return (None, vec![range.clone()]);
+        }
+
+        let mut rest = Vec::new();
+        if range.start < start {
+            rest.push(range.start..start);
+        }
+        if end < range.end {
+            rest.push(end..range.end);
+        }
+        let converted = start - self.source + self.dest..end - self.source + self.dest;
+        (Some(converted), rest)
     }
 }
 
 
 
-#[derive(Debug)]
+impl fmt::Display for Converter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offset = self.dest as i128 - self.source as i128;
+        write!(f, "{:?} -> {:?} ({:+})", self.source_range(), self.dest_range(), offset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct ConverterMap {
     convert_ranges: Vec<Converter>,
 }
@@ -39,128 +70,320 @@ impl ConverterMap {
         }
         value
     }
-}
 
-fn part1() -> u64 {
-    let file = fs::read_to_string("src/23/day5/input.txt").expect("error");
-    let groups: Vec<&str> = file.split("\n\n").collect();
-
-    let seeds: Vec<u64> = groups.get(0).unwrap()
-        .split_once(':').unwrap().1
-        .split_whitespace()
-        .map(|s| s.parse::<u64>().unwrap()).collect();
-
-    let mut converters_maps: Vec<ConverterMap> = Vec::new();
-    for group in groups {
-        let lines = group.split('\n');
-        let mut converters: Vec<Converter> = Vec::new();
-        for (i, line) in lines.enumerate() {
-            if i == 0 {
-                continue;
+    fn convert_range(&self, range: Range<u64>) -> Vec<Range<u64>> {
+        let mut converted = Vec::new();
+        let mut pending = vec![range];
+        for c in &self.convert_ranges {
+            let mut rest = Vec::new();
+            for r in &pending {
+                let (mapped, unmapped) = c.convert_range(r);
+                converted.extend(mapped);
+                rest.extend(unmapped);
             }
-            let nums: Vec<u64> = line.split_whitespace().map(|s| s.parse().unwrap()).collect();
-            // skip last line
-            if nums.len() == 0 {
-                continue;
+            pending = rest;
+        }
+        // values outside every converter keep their number
+        converted.extend(pending);
+        merge(converted)
+    }
+
+    // every piece of the map sorted by source, including the identity gaps
+    // between converters, so together they cover all of 0..u64::MAX
+    fn pieces(&self) -> Vec<Converter> {
+        let mut sorted: Vec<&Converter> = self.convert_ranges.iter().collect();
+        sorted.sort_by_key(|c| c.source);
+
+        let identity = |source: u64, end: u64| Converter { source, dest: source, range: end - source };
+        let mut pieces = Vec::new();
+        let mut position = 0;
+        for c in sorted {
+            if c.source > position {
+                pieces.push(identity(position, c.source));
             }
-            assert!(nums.len() == 3);
-            converters.push(Converter {
-                dest: *nums.get(0).unwrap(),
-                source: *nums.get(1).unwrap(),
-                range: *nums.get(2).unwrap(),
-            })
+            pieces.push(c.clone());
+            position = c.source + c.range;
+        }
+        if position < u64::MAX {
+            pieces.push(identity(position, u64::MAX));
+        }
+        pieces
+    }
 
+    // sorted, non-overlapping converters where neighbours with the same
+    // offset are joined and identity converters are dropped
+    fn normalise(&self) -> ConverterMap {
+        let mut sorted = self.convert_ranges.clone();
+        sorted.retain(|c| c.source != c.dest && c.range > 0);
+        sorted.sort_by_key(|c| c.source);
+
+        let mut convert_ranges: Vec<Converter> = Vec::new();
+        for c in sorted {
+            match convert_ranges.last_mut() {
+                Some(last) if last.source + last.range == c.source && last.dest + last.range == c.dest => {
+                    last.range += c.range;
+                }
+                _ => convert_ranges.push(c),
+            }
         }
-        converters_maps.push(ConverterMap {
-            convert_ranges: converters
+        ConverterMap { convert_ranges }
+    }
 
-        })
+    // single map doing `self` and then `next`
+    fn compose(&self, next: &ConverterMap) -> ConverterMap {
+        let next_pieces = next.pieces();
+        let mut convert_ranges = Vec::new();
+        for piece in self.pieces() {
+            let image = piece.dest_range();
+            for n in &next_pieces {
+                let start = image.start.max(n.source);
+                let end = image.end.min(n.source + n.range);
+                if start >= end {
+                    continue;
+                }
+                convert_ranges.push(Converter {
+                    source: start - piece.dest + piece.source,
+                    dest: start - n.source + n.dest,
+                    range: end - start,
+                });
+            }
+        }
+        ConverterMap { convert_ranges }.normalise()
     }
-    let locations: Vec<u64> = seeds.into_iter().map(|l| {
-        let mut current_num = l;
-        for converter in &converters_maps {
-            current_num = converter.convert(current_num);
+
+    fn compile(converters_maps: &[&ConverterMap]) -> ConverterMap {
+        converters_maps.iter().fold(ConverterMap { convert_ranges: vec![] }, |acc, m| acc.compose(m))
+    }
+
+    // the map the other way around, sorted by what is now the source. A map
+    // doesn't have to be one-to-one so these pieces may overlap
+    fn inverse(&self) -> Vec<Converter> {
+        let mut inverse: Vec<Converter> = self.pieces().into_iter().map(|c| Converter {
+            source: c.dest,
+            dest: c.source,
+            range: c.range,
+        }).collect();
+        inverse.sort_by_key(|c| (c.source, c.dest));
+        inverse
+    }
+
+    // all values that convert into `value`
+    fn preimage(&self, value: u64) -> Vec<u64> {
+        let mut values: Vec<u64> = self.inverse().iter().filter_map(|c| c.convert(value)).collect();
+        values.sort();
+        values
+    }
+}
+
+impl fmt::Display for ConverterMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in &self.convert_ranges {
+            writeln!(f, "{}", c)?;
         }
-        current_num
-    }).collect();
-    *locations.iter().min().unwrap()
+        Ok(())
+    }
 }
 
-// TODO: solution
-// 'compile' all Converters into one.
-// find smallest range and try find a value in range
-fn part2() -> u64 {
-    let file = fs::read_to_string("src/23/day5/input.txt").expect("error");
-    let groups: Vec<&str> = file.split("\n\n").collect();
-
-    let seed_maps: Vec<u64> = groups.get(0).unwrap()
-        .split_once(':').unwrap().1
-        .split_whitespace()
-        .map(|s| s.parse::<u64>().unwrap()).collect();
-    let mut seeds: Vec<u64> = Vec::new();
-    dbg!(&seed_maps);
-    for i in 0..(seed_maps.len() / 2)  {
-        dbg!(i);
-        let first = *seed_maps.get(i * 2).unwrap();
-        let second = *seed_maps.get(i * 2 + 1).unwrap();
-        for i in 0..second {
-            seeds.push(first + i);
-        }
-    }
-    //dbg!(&seeds);
-
-    let mut converters_maps: Vec<ConverterMap> = Vec::new();
-    let mut a = 0;
-    for group in groups {
-        dbg!(a);
-        a = a + 1;
-        let lines = group.split('\n');
-        let mut converters: Vec<Converter> = Vec::new();
-        for (i, line) in lines.enumerate() {
-            if i == 0 {
-                continue;
+// sort and join overlapping or touching ranges
+fn merge(mut ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    ranges.retain(|r| !r.is_empty());
+    ranges.sort_by_key(|r| r.start);
+
+    let mut merged: Vec<Range<u64>> = Vec::new();
+    for r in ranges {
+        match merged.last_mut() {
+            Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+            _ => merged.push(r),
+        }
+    }
+    merged
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum AlmanacError {
+    Header(String),
+    Line(String),
+    UnknownCategory(String),
+    Missing { from: String, to: String },
+    Ambiguous { from: String, to: String, paths: usize },
+}
+
+impl fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlmanacError::Header(header) => write!(f, "invalid map header: {:?}", header),
+            AlmanacError::Line(line) => write!(f, "invalid map line: {:?}", line),
+            AlmanacError::UnknownCategory(c) => write!(f, "unknown category: {}", c),
+            AlmanacError::Missing { from, to } => write!(f, "no maps lead from {} to {}", from, to),
+            AlmanacError::Ambiguous { from, to, paths } => {
+                write!(f, "{} different map chains lead from {} to {}", paths, from, to)
             }
-            let nums: Vec<u64> = line.split_whitespace().map(|s| s.parse().unwrap()).collect();
-            // skip last line
-            if nums.len() == 0 {
+        }
+    }
+}
+
+// "seed-to-soil map:" followed by its converters
+#[derive(Debug)]
+struct CategoryMap {
+    from: String,
+    to: String,
+    map: ConverterMap,
+}
+
+#[derive(Debug)]
+struct Almanac {
+    seeds: Vec<u64>,
+    maps: Vec<CategoryMap>,
+}
+
+impl Almanac {
+    fn new(file: &str) -> Result<Self, AlmanacError> {
+        let mut groups = file.split("\n\n");
+
+        let header = groups.next().unwrap_or_default();
+        let seeds: Vec<u64> = header
+            .strip_prefix("seeds:")
+            .ok_or_else(|| AlmanacError::Header(header.to_owned()))?
+            .split_whitespace()
+            .map(|s| s.parse::<u64>().map_err(|_| AlmanacError::Header(header.to_owned())))
+            .collect::<Result<_, _>>()?;
+
+        let mut maps = Vec::new();
+        for group in groups.filter(|g| !g.trim().is_empty()) {
+            let mut lines = group.lines();
+            let header = lines.next().unwrap_or_default();
+            let (from, to) = header
+                .strip_suffix(" map:")
+                .and_then(|h| h.split_once("-to-"))
+                .ok_or_else(|| AlmanacError::Header(header.to_owned()))?;
+
+            let mut converters: Vec<Converter> = Vec::new();
+            for line in lines.filter(|l| !l.trim().is_empty()) {
+                let nums: Vec<u64> = line
+                    .split_whitespace()
+                    .map(|s| s.parse().map_err(|_| AlmanacError::Line(line.to_owned())))
+                    .collect::<Result<_, _>>()?;
+                if nums.len() != 3 {
+                    return Err(AlmanacError::Line(line.to_owned()));
+                }
+                converters.push(Converter {
+                    dest: nums[0],
+                    source: nums[1],
+                    range: nums[2],
+                })
+            }
+            maps.push(CategoryMap {
+                from: from.to_owned(),
+                to: to.to_owned(),
+                map: ConverterMap { convert_ranges: converters },
+            });
+        }
+        Ok(Almanac { seeds, maps })
+    }
+
+    fn has_category(&self, category: &str) -> bool {
+        self.maps.iter().any(|m| m.from == category || m.to == category)
+    }
+
+    // every chain of maps leading from `from` to `to` without visiting a
+    // category twice, as indices into `maps`
+    fn paths(&self, from: &str, to: &str, visited: &mut Vec<String>) -> Vec<Vec<usize>> {
+        if from == to {
+            return vec![vec![]];
+        }
+        visited.push(from.to_owned());
+        let mut paths = Vec::new();
+        for (i, m) in self.maps.iter().enumerate() {
+            if m.from != from || visited.contains(&m.to) {
                 continue;
             }
-            assert!(nums.len() == 3);
-            converters.push(Converter {
-                dest: *nums.get(0).unwrap(),
-                source: *nums.get(1).unwrap(),
-                range: *nums.get(2).unwrap(),
-            })
-
-        }
-        converters_maps.push(ConverterMap {
-            convert_ranges: converters
-
-        })
-    }
-    //let a 1_879_881_983
-    let len: f64 = seeds.len() as f64;
-    let mut a: f64 = 0.0;
-    let locations: Vec<u64> = seeds.into_iter().map(|l| {
-        dbg!(a / len, a, len);
-        a = a + 1.0;
-        let mut current_num = l;
+            for mut path in self.paths(&m.to, to, visited) {
+                path.insert(0, i);
+                paths.push(path);
+            }
+        }
+        visited.pop();
+        paths
+    }
+
+    fn chain(&self, from: &str, to: &str) -> Result<Vec<&ConverterMap>, AlmanacError> {
+        for category in [from, to] {
+            if !self.has_category(category) {
+                return Err(AlmanacError::UnknownCategory(category.to_owned()));
+            }
+        }
+
+        let mut paths = self.paths(from, to, &mut vec![]);
+        match paths.len() {
+            0 => Err(AlmanacError::Missing { from: from.to_owned(), to: to.to_owned() }),
+            1 => Ok(paths.remove(0).into_iter().map(|i| &self.maps[i].map).collect()),
+            n => Err(AlmanacError::Ambiguous { from: from.to_owned(), to: to.to_owned(), paths: n }),
+        }
+    }
+
+    fn mapping(&self, from: &str, to: &str) -> Result<ConverterMap, AlmanacError> {
+        Ok(ConverterMap::compile(&self.chain(from, to)?))
+    }
+}
+
+fn location_ranges(mut ranges: Vec<Range<u64>>, converters_maps: &[&ConverterMap]) -> Vec<Range<u64>> {
+    for converter in converters_maps {
+        ranges = merge(ranges.into_iter().flat_map(|r| converter.convert_range(r)).collect());
+    }
+    ranges
+}
+
+fn part1() -> u64 {
+    let file = fs::read_to_string("src/23/day5/input.txt").expect("error");
+    let almanac = Almanac::new(&file).unwrap();
+    let converters_maps = almanac.chain("seed", "location").unwrap();
+
+    almanac.seeds.iter().map(|l| {
+        let mut current_num = *l;
         for converter in &converters_maps {
             current_num = converter.convert(current_num);
         }
         current_num
-    }).collect();
-    *locations.iter().min().unwrap()
+    }).min().unwrap()
+}
+
+fn part2() -> u64 {
+    let file = fs::read_to_string("src/23/day5/input.txt").expect("error");
+    let almanac = Almanac::new(&file).unwrap();
+    let converters_maps = almanac.chain("seed", "location").unwrap();
+
+    let ranges: Vec<Range<u64>> = almanac.seeds
+        .chunks(2)
+        .map(|pair| pair[0]..pair[0] + pair[1])
+        .collect();
+
+    location_ranges(ranges, &converters_maps).first().unwrap().start
 }
 
 fn main() {
     println!("p1: {}", part1());
-    println!("p2: {}", part2());
+    let p2 = part2();
+    println!("p2: {}", p2);
+
+    let file = fs::read_to_string("src/23/day5/input.txt").expect("error");
+    let compiled = Almanac::new(&file).unwrap().mapping("seed", "location").unwrap();
+    print!("seed-to-location map:\n{}", compiled);
+    println!("seeds for location {}: {:?}", p2, compiled.preimage(p2));
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{Converter, ConverterMap};
+    use std::ops::Range;
+
+    use test_case::test_case;
+
+    use crate::{Almanac, AlmanacError, Converter, ConverterMap, location_ranges, merge};
+
+    const EXAMPLE: &str = "seeds: 79 14 55 13\n\nseed-to-soil map:\n50 98 2\n52 50 48\n\nsoil-to-fertilizer map:\n0 15 37\n37 52 2\n39 0 15\n\nfertilizer-to-water map:\n49 53 8\n0 11 42\n42 0 7\n57 7 4\n\nwater-to-light map:\n88 18 7\n18 25 70\n\nlight-to-temperature map:\n45 77 23\n81 45 19\n68 64 13\n\ntemperature-to-humidity map:\n0 69 1\n1 0 69\n\nhumidity-to-location map:\n60 56 37\n56 93 4\n";
+
+    fn bounds(ranges: &[Range<u64>]) -> Vec<(u64, u64)> {
+        ranges.iter().map(|r| (r.start, r.end)).collect()
+    }
 
 
     #[test]
@@ -175,4 +398,132 @@ mod test {
         assert_eq!(converter_map.convert(2), 11);
         assert_eq!(converter_map.convert(3), 3);
     }
+
+    #[test]
+    fn test_convert_range() {
+        let converter = Converter { source: 10, dest: 100, range: 5 };
+
+        let (converted, rest) = converter.convert_range(&(0..5));
+        assert_eq!((converted, bounds(&rest)), (None, vec![(0, 5)]));
+        let (converted, rest) = converter.convert_range(&(11..13));
+        assert_eq!((converted, bounds(&rest)), (Some(101..103), vec![]));
+        let (converted, rest) = converter.convert_range(&(8..12));
+        assert_eq!((converted, bounds(&rest)), (Some(100..102), vec![(8, 10)]));
+        let (converted, rest) = converter.convert_range(&(5..20));
+        assert_eq!((converted, bounds(&rest)), (Some(100..105), vec![(5, 10), (15, 20)]));
+    }
+
+    #[test]
+    fn test_converter_map_range() {
+        // seed-to-soil map from the example
+        let converter_map = ConverterMap {
+            convert_ranges: vec![
+                Converter { source: 98, dest: 50, range: 2 },
+                Converter { source: 50, dest: 52, range: 48 },
+            ],
+        };
+
+        assert_eq!(bounds(&converter_map.convert_range(79..93)), vec![(81, 95)]);
+        assert_eq!(bounds(&converter_map.convert_range(40..60)), vec![(40, 50), (52, 62)]);
+        assert_eq!(bounds(&converter_map.convert_range(95..105)), vec![(50, 52), (97, 105)]);
+
+        let converted: Vec<u64> = converter_map.convert_range(0..120).into_iter().flatten().collect();
+        let mut single: Vec<u64> = (0..120).map(|v| converter_map.convert(v)).collect();
+        single.sort();
+        assert_eq!(converted, single);
+    }
+
+    #[test]
+    fn test_merge() {
+        assert_eq!(bounds(&merge(vec![5..8, 0..2, 2..3, 7..10, 4..4])), vec![(0, 3), (5, 10)]);
+    }
+
+    #[test]
+    fn test_compose() {
+        let first = ConverterMap {
+            convert_ranges: vec![Converter { source: 10, dest: 20, range: 10 }],
+        };
+        let second = ConverterMap {
+            convert_ranges: vec![Converter { source: 15, dest: 0, range: 10 }],
+        };
+        let composed = first.compose(&second);
+
+        for value in 0..40 {
+            assert_eq!(composed.convert(value), second.convert(first.convert(value)), "{}", value);
+        }
+        assert_eq!(composed.to_string(), "10..15 -> 5..10 (-5)\n15..20 -> 25..30 (+10)\n20..25 -> 5..10 (-15)\n");
+    }
+
+    #[test]
+    fn test_normalise() {
+        let converter_map = ConverterMap {
+            convert_ranges: vec![
+                Converter { source: 5, dest: 15, range: 5 },
+                Converter { source: 0, dest: 10, range: 5 },
+                Converter { source: 20, dest: 20, range: 5 },
+            ],
+        };
+        assert_eq!(converter_map.normalise().convert_ranges, vec![Converter { source: 0, dest: 10, range: 10 }]);
+    }
+
+    #[test]
+    fn test_compile_example() {
+        let almanac = Almanac::new(EXAMPLE).unwrap();
+        let converters_maps = almanac.chain("seed", "location").unwrap();
+        let compiled = almanac.mapping("seed", "location").unwrap();
+
+        for seed in 0..200 {
+            let location = converters_maps.iter().fold(seed, |v, m| m.convert(v));
+            assert_eq!(compiled.convert(seed), location);
+            assert!(compiled.preimage(location).contains(&seed));
+        }
+        let locations: Vec<u64> = almanac.seeds.iter().map(|s| compiled.convert(*s)).collect();
+        assert_eq!(locations, vec![82, 43, 86, 35]);
+        assert_eq!(compiled.preimage(46), vec![82]);
+    }
+
+    #[test]
+    fn test_example() {
+        let almanac = Almanac::new(EXAMPLE).unwrap();
+        let converters_maps = almanac.chain("seed", "location").unwrap();
+        let ranges = almanac.seeds.chunks(2).map(|pair| pair[0]..pair[0] + pair[1]).collect();
+        assert_eq!(location_ranges(ranges, &converters_maps).first().unwrap().start, 46);
+    }
+
+    #[test]
+    fn test_chain() {
+        let almanac = Almanac::new(EXAMPLE).unwrap();
+        assert_eq!(almanac.maps.len(), 7);
+        assert_eq!(almanac.chain("seed", "location").unwrap().len(), 7);
+        assert_eq!(almanac.chain("soil", "humidity").unwrap().len(), 5);
+        assert_eq!(almanac.chain("light", "light").unwrap().len(), 0);
+
+        // seed 79 is soil 81 and humidity 78
+        let soil_to_humidity = almanac.mapping("soil", "humidity").unwrap();
+        assert_eq!(soil_to_humidity.convert(81), 78);
+    }
+
+    #[test_case("location", "seed", AlmanacError::Missing { from: "location".to_owned(), to: "seed".to_owned() })]
+    #[test_case("seed", "dirt", AlmanacError::UnknownCategory("dirt".to_owned()))]
+    fn test_chain_error(from: &str, to: &str, error: AlmanacError) {
+        let almanac = Almanac::new(EXAMPLE).unwrap();
+        assert_eq!(almanac.chain(from, to).unwrap_err(), error);
+    }
+
+    #[test]
+    fn test_chain_ambiguous() {
+        let almanac = Almanac::new("seeds: 1\n\nseed-to-soil map:\n0 1 1\n\nsoil-to-water map:\n\nseed-to-water map:\n").unwrap();
+        assert_eq!(
+            almanac.chain("seed", "water").unwrap_err(),
+            AlmanacError::Ambiguous { from: "seed".to_owned(), to: "water".to_owned(), paths: 2 }
+        );
+        assert_eq!(almanac.chain("soil", "water").unwrap().len(), 1);
+    }
+
+    #[test_case("seeds: 1 2\n\nseed to soil map:\n1 2 3", AlmanacError::Header("seed to soil map:".to_owned()))]
+    #[test_case("seeds: 1 2\n\nseed-to-soil map:\n1 2", AlmanacError::Line("1 2".to_owned()))]
+    #[test_case("seed: 1 2", AlmanacError::Header("seed: 1 2".to_owned()))]
+    fn test_parse_error(file: &str, error: AlmanacError) {
+        assert_eq!(Almanac::new(file).unwrap_err(), error);
+    }
 }
@@ -1,63 +1,421 @@
-use std::fs;
+use std::{fs, ops::RangeInclusive};
 
+use num::BigUint;
 
-fn simulate(hold_time: u64, max_time: u64) -> u64 {
+
+// distance travelled, None if it doesn't fit in a u128
+fn simulate(hold_time: u128, max_time: u128) -> Option<u128> {
     let speed = hold_time;
     let run_time = max_time - hold_time;
-    speed * run_time
+    speed.checked_mul(run_time)
 }
 
-fn part1() -> u64 {
-    let string = fs::read_to_string("src/23/day6/input.txt").unwrap();
-    let lines: Vec<&str> = string.lines().collect();
+fn wins(hold_time: u128, time: u128, distance: u128) -> bool {
+    simulate(hold_time, time).is_none_or(|d| d > distance)
+}
 
-    let time_str: &str = lines.get(0).unwrap();
-    let distance_str: &str = lines.get(1).unwrap();
+// hold times h where h * (time - h) > distance lie strictly between the roots
+// of h^2 - time * h + distance = 0, i.e. (time ± sqrt(time^2 - 4 * distance)) / 2.
+// The integer square root can put the lower bound one off, so step it onto the
+// first winning hold time. The winning range is symmetric around time / 2.
+fn winning_holds(time: u128, distance: u128) -> Option<RangeInclusive<u128>> {
+    let square = BigUint::from(time) * time;
+    let four_distance = BigUint::from(distance) * 4u32;
+    if square < four_distance {
+        return None;
+    }
+    let root: u128 = (square - four_distance).sqrt().try_into().unwrap();
 
-    let (_, times_str) = time_str.split_once(':').unwrap();
-    let (_, distances_str) = distance_str.split_once(':').unwrap();
-    let times: Vec<u64> = times_str.split_whitespace().map(|n| n.parse().unwrap()).collect();
-    let distances: Vec<u64> = distances_str.split_whitespace().map(|n| n.parse().unwrap()).collect();
+    let half = time / 2;
+    let mut low = (time - root) / 2;
+    while low > 0 && wins(low - 1, time, distance) {
+        low -= 1;
+    }
+    while low <= half && !wins(low, time, distance) {
+        low += 1;
+    }
+    if low > half {
+        return None;
+    }
+    Some(low..=time - low)
+}
 
-    let mut win_counts = Vec::new();
-    for (time, distance) in times.iter().zip(distances) {
-        let mut win_count = 0;
-        for t in 0..*time {
-            if simulate(t, *time) > distance {
-                win_count = win_count + 1;
-            }
+// smallest value in lo..=hi where `pred` holds, for a `pred` that stays true
+// once it is
+fn first_true(mut lo: u128, mut hi: u128, pred: impl Fn(u128) -> bool) -> Option<u128> {
+    if !pred(hi) {
+        return None;
+    }
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
         }
-        win_counts.push(win_count);
     }
+    Some(lo)
+}
 
-    win_counts.into_iter().reduce(|a, b| a * b).unwrap()
+fn ways_to_win(time: u128, distance: u128) -> u128 {
+    Boat::default().ways_to_win(&Race { time, distance })
 }
 
-fn part2() -> u64 {
-    let string = fs::read_to_string("src/23/day6/input.txt").unwrap();
-    let lines: Vec<&str> = string.lines().collect();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Race {
+    time: u128,
+    distance: u128,
+}
 
-    let time_str: &str = lines.get(0).unwrap();
-    let distance_str: &str = lines.get(1).unwrap();
+// holding the button for h ms charges the boat to `charge_rate * h` speed, up
+// to `max_speed`. With `drag` the boat loses that much speed every ms it runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Boat {
+    charge_rate: u128,
+    max_speed: Option<u128>,
+    drag: Option<u128>,
+}
 
-    let (_, times_str) = time_str.split_once(':').unwrap();
-    let (_, distances_str) = distance_str.split_once(':').unwrap();
-    let time: u64 = times_str.chars().filter(|c| !c.is_whitespace()).collect::<String>().parse().unwrap();
-    let distance: u64 = distances_str.chars().filter(|c| !c.is_whitespace()).collect::<String>().parse().unwrap();
+impl Default for Boat {
+    fn default() -> Self {
+        Boat { charge_rate: 1, max_speed: None, drag: None }
+    }
+}
+
+impl Boat {
+    fn speed(&self, hold_time: u128) -> Option<u128> {
+        let speed = self.charge_rate.checked_mul(hold_time)?;
+        Some(self.max_speed.map_or(speed, |max| speed.min(max)))
+    }
 
-    let mut win_counts = Vec::new();
-    let mut win_count = 0;
-    for t in 0..time {
-        if simulate(t, time) > distance {
-            win_count = win_count + 1;
+    // last hold time where the speed still grows with the hold time
+    fn charge_limit(&self, time: u128) -> u128 {
+        match self.max_speed {
+            Some(max) if self.charge_rate > 0 => (max / self.charge_rate).min(time),
+            Some(_) => time,
+            None => time,
         }
     }
-    win_counts.push(win_count);
 
-    win_counts.into_iter().reduce(|a, b| a * b).unwrap()
+    // distance travelled, None if it doesn't fit in a u128
+    fn distance(&self, hold_time: u128, time: u128) -> Option<u128> {
+        let speed = self.speed(hold_time)?;
+        let run_time = time - hold_time;
+        match self.drag {
+            Some(drag) if drag > 0 => {
+                // moves for n ms: speed + (speed - drag) + ... + (speed - (n - 1) * drag)
+                let n = run_time.min(speed.div_ceil(drag));
+                // n * (n - 1) / 2, halving the even factor first
+                let pairs = match n % 2 {
+                    0 => (n / 2).checked_mul(n.saturating_sub(1))?,
+                    _ => n.checked_mul((n - 1) / 2)?,
+                };
+                let lost = drag.checked_mul(pairs)?;
+                speed.checked_mul(n)?.checked_sub(lost)
+            }
+            _ => speed.checked_mul(run_time),
+        }
+    }
+
+    fn beats(&self, hold_time: u128, race: &Race) -> bool {
+        self.distance(hold_time, race.time).is_none_or(|d| d > race.distance)
+    }
+
+    fn winning(&self, race: &Race) -> Vec<RangeInclusive<u128>> {
+        if self.drag.is_some_and(|d| d > 0) {
+            return self.winning_with_drag(race);
+        }
+        if self.charge_rate == 0 {
+            return vec![];
+        }
+
+        let mut intervals = Vec::new();
+        // charging: charge_rate * h * (time - h) > distance, the left side is
+        // a multiple of charge_rate so this is h * (time - h) > distance / charge_rate
+        let limit = self.charge_limit(race.time);
+        if let Some(holds) = winning_holds(race.time, race.distance / self.charge_rate) {
+            let end = (*holds.end()).min(limit);
+            if *holds.start() <= end {
+                intervals.push(*holds.start()..=end);
+            }
+        }
+        // capped: max_speed * (time - h) > distance
+        if let Some(max) = self.max_speed.filter(|m| *m > 0) {
+            let first = limit + 1;
+            if let Some(last) = race.time.checked_sub(race.distance / max + 1) {
+                if first <= last {
+                    match intervals.last_mut() {
+                        Some(previous) if previous.end() + 1 == first => {
+                            *previous = *previous.start()..=last;
+                        }
+                        _ => intervals.push(first..=last),
+                    }
+                }
+            }
+        }
+        intervals
+    }
+
+    // with drag the distance has two regimes. While the run is too short for
+    // the boat to stop it is s * r - drag * r * (r - 1) / 2 for run time r, and
+    // that is concave in the hold time on either side of the charge limit.
+    // Once the boat stops before the end the distance only depends on the
+    // speed, so it doesn't go down as the hold time grows. Every piece rises
+    // and then falls, so each one is searched with a handful of distances
+    fn drag_pieces(&self, time: u128) -> Vec<RangeInclusive<u128>> {
+        let drag = self.drag.unwrap();
+        let moving = |h: u128| self.speed(h).is_none_or(|s| time - h <= s.div_ceil(drag));
+        let stops_until = first_true(0, time, moving).unwrap();
+        let limit = self.charge_limit(time);
+
+        let mut pieces = Vec::new();
+        if stops_until > 0 {
+            pieces.push(0..=stops_until - 1);
+        }
+        if stops_until <= limit {
+            pieces.push(stops_until..=limit);
+        }
+        if limit < time {
+            pieces.push((limit + 1).max(stops_until)..=time);
+        }
+        pieces
+    }
+
+    // overflowing distances are further than any race
+    fn reach(&self, hold_time: u128, time: u128) -> u128 {
+        self.distance(hold_time, time).unwrap_or(u128::MAX)
+    }
+
+    // first hold time of the furthest distance in a piece that rises and then falls
+    fn peak(&self, piece: &RangeInclusive<u128>, time: u128) -> u128 {
+        let (start, end) = (*piece.start(), *piece.end());
+        first_true(start, end, |h| h == end || self.reach(h, time) >= self.reach(h + 1, time)).unwrap()
+    }
+
+    fn winning_with_drag(&self, race: &Race) -> Vec<RangeInclusive<u128>> {
+        let mut intervals: Vec<RangeInclusive<u128>> = Vec::new();
+        for piece in self.drag_pieces(race.time) {
+            let peak = self.peak(&piece, race.time);
+            let beats = |h| self.beats(h, race);
+            if !beats(peak) {
+                continue;
+            }
+            let first = first_true(*piece.start(), peak, beats).unwrap();
+            let last = match first_true(peak, *piece.end(), |h| !beats(h)) {
+                Some(h) => h - 1,
+                None => *piece.end(),
+            };
+            match intervals.last_mut() {
+                Some(previous) if previous.end() + 1 == first => {
+                    *previous = *previous.start()..=last;
+                }
+                _ => intervals.push(first..=last),
+            }
+        }
+        intervals
+    }
+
+    fn ways_to_win(&self, race: &Race) -> u128 {
+        self.winning(race).iter().map(|r| r.end() - r.start() + 1).sum()
+    }
+
+    // hold time going the furthest, the shortest one if several do
+    fn optimal_hold(&self, time: u128) -> u128 {
+        let further = |a: u128, b: u128| match (self.distance(a, time), self.distance(b, time)) {
+            (Some(da), Some(db)) => db > da,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        let candidates: Vec<u128> = if self.drag.is_some_and(|d| d > 0) {
+            self.drag_pieces(time).iter().map(|p| self.peak(p, time)).collect()
+        } else if self.charge_rate == 0 {
+            vec![0]
+        } else {
+            // h * (time - h) peaks at time / 2, once capped it only goes down
+            let limit = self.charge_limit(time);
+            let mut candidates = vec![(time / 2).min(limit)];
+            if limit < time {
+                candidates.push(limit + 1);
+            }
+            candidates
+        };
+        candidates.into_iter().reduce(|best, h| if further(best, h) { h } else { best }).unwrap()
+    }
+
+    fn margin(&self, races: &[Race]) -> u128 {
+        races.iter().map(|r| self.ways_to_win(r)).product()
+    }
+}
+
+fn parse_races(string: &str) -> Vec<Race> {
+    let lines: Vec<&str> = string.lines().collect();
+
+    let (_, times_str) = lines[0].split_once(':').unwrap();
+    let (_, distances_str) = lines[1].split_once(':').unwrap();
+    let times = times_str.split_whitespace().map(|n| n.parse().unwrap());
+    let distances = distances_str.split_whitespace().map(|n| n.parse().unwrap());
+
+    times.zip(distances).map(|(time, distance)| Race { time, distance }).collect()
+}
+
+fn part1() -> u128 {
+    let string = fs::read_to_string("src/23/day6/input.txt").unwrap();
+    Boat::default().margin(&parse_races(&string))
+}
+
+fn part2() -> u128 {
+    let string = fs::read_to_string("src/23/day6/input.txt").unwrap();
+    let lines: Vec<&str> = string.lines().collect();
+
+    let (_, times_str) = lines[0].split_once(':').unwrap();
+    let (_, distances_str) = lines[1].split_once(':').unwrap();
+    let time: u128 = times_str.chars().filter(|c| !c.is_whitespace()).collect::<String>().parse().unwrap();
+    let distance: u128 = distances_str.chars().filter(|c| !c.is_whitespace()).collect::<String>().parse().unwrap();
+
+    ways_to_win(time, distance)
 }
 
 fn main() {
     println!("p1: {}", part1());
     println!("p2: {}", part2());
+
+    let string = fs::read_to_string("src/23/day6/input.txt").unwrap();
+    let boat = Boat::default();
+    for race in parse_races(&string) {
+        println!("race {:?}: wins {:?}, best hold {}", race, boat.winning(&race), boat.optimal_hold(race.time));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use crate::{simulate, ways_to_win, Boat, Race};
+
+    fn brute_force(time: u128, distance: u128) -> u128 {
+        (0..=time).filter(|t| simulate(*t, time).unwrap() > distance).count() as u128
+    }
+
+    #[test_case(7, 9, 4)]
+    #[test_case(15, 40, 8)]
+    #[test_case(30, 200, 9)]
+    #[test_case(71530, 940200, 71503)]
+    // ties at 2 * 2 = 4 don't count
+    #[test_case(4, 4, 0)]
+    #[test_case(4, 3, 1)]
+    #[test_case(0, 0, 0)]
+    #[test_case(1, 0, 0)]
+    #[test_case(2, 0, 1)]
+    fn test_ways_to_win(time: u128, distance: u128, ways: u128) {
+        assert_eq!(ways_to_win(time, distance), ways);
+        assert_eq!(brute_force(time, distance), ways);
+    }
+
+    #[test]
+    fn test_random_against_brute_force() {
+        // small lcg so the cases are the same every run
+        let mut seed: u64 = 0x2023_0006;
+        let mut next = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as u128
+        };
+        for _ in 0..2000 {
+            let time = next() % 500;
+            let distance = next() % (time * time / 4 + 10);
+            assert_eq!(ways_to_win(time, distance), brute_force(time, distance), "{} {}", time, distance);
+        }
+    }
+
+    #[test]
+    fn test_u128() {
+        let time = u128::MAX;
+        // distance 0 is won by every hold time except 0 and time
+        assert_eq!(ways_to_win(time, 0), time - 1);
+        // h * (time - h) > time - 1 for every h in 2..=time-2
+        assert_eq!(ways_to_win(time, time - 1), time - 3);
+
+        let time: u128 = 1 << 64;
+        let half = time / 2;
+        assert_eq!(ways_to_win(time, half * half - 1), 1);
+        assert_eq!(ways_to_win(time, half * half), 0);
+    }
+
+    fn brute_force_boat(boat: &Boat, race: &Race) -> u128 {
+        (0..=race.time).filter(|h| boat.distance(*h, race.time).unwrap() > race.distance).count() as u128
+    }
+
+    #[test]
+    fn test_boat_default() {
+        let races = [Race { time: 7, distance: 9 }, Race { time: 15, distance: 40 }, Race { time: 30, distance: 200 }];
+        let boat = Boat::default();
+        assert_eq!(boat.margin(&races), 288);
+        assert_eq!(boat.winning(&races[0]), vec![2..=5]);
+        assert_eq!(boat.optimal_hold(7), 3);
+        assert_eq!(boat.optimal_hold(30), 15);
+    }
+
+    #[test_case(Boat { charge_rate: 2, max_speed: None, drag: None })]
+    #[test_case(Boat { charge_rate: 3, max_speed: Some(20), drag: None })]
+    #[test_case(Boat { charge_rate: 1, max_speed: Some(4), drag: None })]
+    #[test_case(Boat { charge_rate: 5, max_speed: Some(3), drag: None })]
+    #[test_case(Boat { charge_rate: 0, max_speed: None, drag: None })]
+    #[test_case(Boat { charge_rate: 2, max_speed: Some(0), drag: None })]
+    #[test_case(Boat { charge_rate: 2, max_speed: None, drag: Some(1) })]
+    #[test_case(Boat { charge_rate: 4, max_speed: Some(30), drag: Some(3) })]
+    #[test_case(Boat { charge_rate: 1, max_speed: Some(7), drag: Some(2) })]
+    #[test_case(Boat { charge_rate: 3, max_speed: None, drag: Some(10) })]
+    #[test_case(Boat { charge_rate: 1, max_speed: Some(0), drag: Some(1) })]
+    fn test_boat_against_brute_force(boat: Boat) {
+        for time in 0..40 {
+            for distance in 0..200 {
+                let race = Race { time, distance };
+                assert_eq!(boat.ways_to_win(&race), brute_force_boat(&boat, &race), "{:?}", race);
+            }
+            let best = (0..=time).map(|h| boat.distance(h, time).unwrap()).max().unwrap();
+            let optimal = boat.optimal_hold(time);
+            assert_eq!(boat.distance(optimal, time).unwrap(), best, "{}", time);
+            assert!((0..optimal).all(|h| boat.distance(h, time).unwrap() < best));
+        }
+    }
+
+    #[test]
+    fn test_drag_large_race() {
+        // a part 2 sized race is searched, not simulated
+        let boat = Boat { charge_rate: 1, max_speed: None, drag: Some(1) };
+        let race = Race { time: 50_000_000, distance: 200_000_000_000_000 };
+        let wins = boat.winning(&race);
+        assert_eq!(wins.len(), 1);
+        let (first, last) = (*wins[0].start(), *wins[0].end());
+        assert!(boat.beats(first, &race) && boat.beats(last, &race));
+        assert!(!boat.beats(first - 1, &race) && !boat.beats(last + 1, &race));
+        let optimal = boat.optimal_hold(race.time);
+        let best = boat.distance(optimal, race.time).unwrap();
+        assert!((optimal - 1000..optimal + 1000).all(|h| boat.distance(h, race.time).unwrap() <= best));
+    }
+
+    #[test]
+    fn test_drag_u128() {
+        let boat = Boat { charge_rate: 1, max_speed: None, drag: Some(1) };
+        // runs for 2^69 ms at up to 2^69 speed, too far for a u128
+        assert_eq!(boat.distance(1 << 69, 1 << 70), None);
+        // stops after one ms
+        let heavy = Boat { charge_rate: 1, max_speed: None, drag: Some(1 << 64) };
+        assert_eq!(heavy.distance(1 << 64, 1 << 65), Some(1 << 64));
+
+        let race = Race { time: 1 << 70, distance: 1 << 100 };
+        let wins = boat.winning(&race);
+        assert_eq!(wins.len(), 1);
+        let (first, last) = (*wins[0].start(), *wins[0].end());
+        assert!(boat.beats(first, &race) && boat.beats(last, &race));
+        assert!(!boat.beats(first - 1, &race) && !boat.beats(last + 1, &race));
+        assert_eq!(boat.ways_to_win(&race), last - first + 1);
+    }
+
+    #[test]
+    fn test_capped_intervals() {
+        // speed 5 from hold 1, so the distance only goes down after that
+        let boat = Boat { charge_rate: 5, max_speed: Some(5), drag: None };
+        assert_eq!(boat.winning(&Race { time: 10, distance: 20 }), vec![1..=5]);
+        assert_eq!(boat.optimal_hold(10), 1);
+    }
 }
@@ -1,134 +1,679 @@
-use std::{fs, cmp::Ordering};
+use std::{env, fmt, fs, cmp::Ordering};
 
 use itertools::Itertools;
 
-#[derive(Debug, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
 enum CamelCardType {
     FiveOfAKind,
+    StraightFlush,
     FourOfAKind,
     FullHouse,
+    Flush,
+    Straight,
     ThreeOfAKind,
     TwoPair,
     OnePair,
     HighCard,
 }
 
-#[derive(Debug, Eq, PartialOrd, PartialEq)]
-struct CamelCard<'a> {
-    hand: &'a str,
-    bid: u32,
+impl CamelCardType {
+    // groups of equal cards the hand has to contain, largest first
+    fn groups(&self) -> Option<&'static [usize]> {
+        use CamelCardType::*;
+        match self {
+            FiveOfAKind => Some(&[5]),
+            FourOfAKind => Some(&[4]),
+            FullHouse => Some(&[3, 2]),
+            ThreeOfAKind => Some(&[3]),
+            TwoPair => Some(&[2, 2]),
+            OnePair => Some(&[2]),
+            HighCard => Some(&[]),
+            StraightFlush | Flush | Straight => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum HandError {
+    Length { expected: usize, found: usize },
+    // the spec's hands have more cards than its sort key can hold
+    KeyWidth { hand_length: usize, max: usize },
+    Card(char),
+    Suit(char),
+    MissingSuit,
+    Bid(String),
+}
+
+impl fmt::Display for HandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandError::Length { expected, found } => {
+                write!(f, "expected {} cards but found {}", expected, found)
+            }
+            HandError::KeyWidth { hand_length, max } => {
+                write!(f, "hands of {} cards don't fit the sort key, at most {} do", hand_length, max)
+            }
+            HandError::Card(c) => write!(f, "unknown card: {:?}", c),
+            HandError::Suit(c) => write!(f, "unknown suit: {:?}", c),
+            HandError::MissingSuit => write!(f, "last card is missing its suit"),
+            HandError::Bid(line) => write!(f, "missing or invalid bid: {:?}", line),
+        }
+    }
 }
 
-impl<'a> CamelCard<'a> {
-    fn camel_type(&self) -> CamelCardType {
-        // TODO
-        let char_counts = self.hand.chars().counts();
-        let max: Vec<(&char, &usize)> = char_counts.iter().max_set_by(|a, b| a.1.cmp(b.1));
-        let card = match max.get(0).unwrap().1 {
-            5 => Some(CamelCardType::FiveOfAKind),
-            4 => Some(CamelCardType::FourOfAKind),
-            3 => {
-                if *char_counts.iter().min_set_by(|a, b| a.1.cmp(b.1)).get(0).unwrap().1 == 2 {
-                    Some(CamelCardType::FullHouse)
-                } else {
-                    Some(CamelCardType::ThreeOfAKind)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TieBreak {
+    // compare the cards left to right as they were dealt
+    InOrder,
+    // compare the most common cards first, then by strength, like in poker
+    Grouped,
+}
+
+// describes a card game, the cards in a hand are a rank char optionally
+// followed by a suit char, whitespace between cards is ignored
+#[derive(Debug, Clone)]
+struct Spec {
+    // weakest to strongest
+    ranks: Vec<char>,
+    // ranks that can stand in for any other card when finding the hand type
+    wildcards: Vec<char>,
+    suits: Option<Vec<char>>,
+    hand_length: usize,
+    // strongest first, hands matching none of them are `HighCard`
+    categories: Vec<CamelCardType>,
+    // the strongest rank can also start a straight below the weakest
+    ace_low: bool,
+    tie_break: TieBreak,
+}
+
+impl Spec {
+    fn camel() -> Self {
+        use CamelCardType::*;
+        Spec {
+            ranks: "23456789TJQKA".chars().collect(),
+            wildcards: vec![],
+            suits: None,
+            hand_length: 5,
+            categories: vec![FiveOfAKind, FourOfAKind, FullHouse, ThreeOfAKind, TwoPair, OnePair, HighCard],
+            ace_low: false,
+            tie_break: TieBreak::InOrder,
+        }
+    }
+
+    // 'J' is a joker, it becomes whatever card makes the best hand but is
+    // the weakest card when comparing cards
+    fn camel_jokers() -> Self {
+        Spec {
+            ranks: "J23456789TQKA".chars().collect(),
+            wildcards: vec!['J'],
+            ..Spec::camel()
+        }
+    }
+
+    fn named(name: &str) -> Option<Self> {
+        match name {
+            "camel" => Some(Spec::camel()),
+            "jokers" => Some(Spec::camel_jokers()),
+            "poker" => Some(Spec::poker()),
+            _ => None,
+        }
+    }
+
+    fn poker() -> Self {
+        use CamelCardType::*;
+        Spec {
+            ranks: "23456789TJQKA".chars().collect(),
+            wildcards: vec![],
+            suits: Some("cdhs".chars().collect()),
+            hand_length: 5,
+            categories: vec![StraightFlush, FourOfAKind, FullHouse, Flush, Straight, ThreeOfAKind, TwoPair, OnePair, HighCard],
+            ace_low: true,
+            tie_break: TieBreak::Grouped,
+        }
+    }
+
+    // (rank, suit) of every card in the hand
+    fn cards(&self, hand: &str) -> Vec<(char, Option<char>)> {
+        let chars: Vec<char> = hand.chars().filter(|c| !c.is_whitespace()).collect();
+        match self.suits {
+            Some(_) => chars.chunks(2).map(|c| (c[0], c.get(1).copied())).collect(),
+            None => chars.into_iter().map(|c| (c, None)).collect(),
+        }
+    }
+
+    // bits per card in the sort key
+    fn strength_bits(&self) -> u32 {
+        u128::BITS - (self.ranks.len() as u128).leading_zeros()
+    }
+
+    // the card strengths have to leave room for the hand type in the key
+    fn max_hand_length(&self) -> usize {
+        119 / self.strength_bits() as usize
+    }
+
+    fn validate(&self, hand: &str) -> Result<(), HandError> {
+        if self.hand_length > self.max_hand_length() {
+            return Err(HandError::KeyWidth { hand_length: self.hand_length, max: self.max_hand_length() });
+        }
+        let cards = self.cards(hand);
+        if cards.len() != self.hand_length {
+            return Err(HandError::Length { expected: self.hand_length, found: cards.len() });
+        }
+        for (rank, suit) in cards {
+            if !self.ranks.contains(&rank) {
+                return Err(HandError::Card(rank));
+            }
+            if let Some(suits) = &self.suits {
+                let suit = suit.ok_or(HandError::MissingSuit)?;
+                if !suits.contains(&suit) {
+                    return Err(HandError::Suit(suit));
                 }
-            },
-            _ => None
+            }
+        }
+        Ok(())
+    }
+
+    fn is_wild(&self, rank: char) -> bool {
+        self.wildcards.contains(&rank)
+    }
+
+    fn strength(&self, rank: char) -> u128 {
+        match self.ranks.iter().position(|r| *r == rank) {
+            Some(i) => i as u128 + 1,
+            None => panic!("error ordering"),
+        }
+    }
+
+    // whether the non wild ranks together with `wild` wildcards make a
+    // straight, `ace_low` lets the strongest rank play below the weakest
+    fn is_straight(&self, ranks: &[char], wild: usize, ace_low: bool) -> bool {
+        let order: Vec<char> = self.ranks.iter().filter(|r| !self.is_wild(**r)).copied().collect();
+        if self.hand_length > order.len() || ranks.len() + wild != self.hand_length {
+            return false;
+        }
+        let mut positions: Vec<i64> = ranks
+            .iter()
+            .map(|r| order.iter().position(|o| o == r).unwrap() as i64)
+            .collect();
+        if ace_low {
+            let top = order.len() as i64 - 1;
+            positions.iter_mut().filter(|p| **p == top).for_each(|p| *p = -1);
+        }
+        positions.sort();
+        if positions.windows(2).any(|w| w[0] == w[1]) {
+            return false;
+        }
+        match (positions.first(), positions.last()) {
+            (Some(first), Some(last)) => ((last - first) as usize) < self.hand_length,
+            _ => true,
+        }
+    }
+
+    fn hand_type(&self, hand: &str) -> CamelCardType {
+        use CamelCardType::*;
+
+        let cards = self.cards(hand);
+        let (wild, fixed): (Vec<_>, Vec<_>) = cards.iter().partition(|(r, _)| self.is_wild(*r));
+        let ranks: Vec<char> = fixed.iter().map(|(r, _)| *r).collect();
+
+        let counts: Vec<usize> = ranks.iter().counts().into_values().sorted().rev().collect();
+        // pairing the largest groups with the largest counts leaves the
+        // fewest cards missing, wildcards have to cover the rest
+        let has_groups = |groups: &[usize]| {
+            let missing: usize = groups
+                .iter()
+                .enumerate()
+                .map(|(i, g)| g.saturating_sub(counts.get(i).copied().unwrap_or(0)))
+                .sum();
+            missing <= wild.len()
         };
 
-        if card.is_none() {
-            return match max.len() {
-                2 => CamelCardType::TwoPair,
-                1 => CamelCardType::OnePair,
-                _ => CamelCardType::HighCard,
+        let flush = || self.suits.is_some() && fixed.iter().map(|(_, s)| s).all_equal();
+        let straight = || {
+            self.is_straight(&ranks, wild.len(), false)
+                || (self.ace_low && self.is_straight(&ranks, wild.len(), true))
+        };
+
+        let matches = |category: &CamelCardType| match category {
+            StraightFlush => straight() && flush(),
+            Flush => flush(),
+            Straight => straight(),
+            _ => has_groups(category.groups().unwrap()),
+        };
+        self.categories.iter().copied().find(matches).unwrap_or(HighCard)
+    }
+
+    // the ranks of the hand with every wildcard replaced by the card it stands
+    // in for: the rest of the best straight, the cards the groups of
+    // `hand_type` are missing (extra ones join the largest group), or for
+    // hands without groups the strongest ranks the hand doesn't have yet
+    fn resolve_wilds(&self, fixed: &[char], wild: usize, hand_type: CamelCardType) -> Vec<char> {
+        use CamelCardType::*;
+
+        let order: Vec<char> = self.ranks.iter().filter(|r| !self.is_wild(**r)).copied().collect();
+        let len = self.hand_length;
+        if matches!(hand_type, Straight | StraightFlush) && order.len() >= len {
+            // straights from the highest down, the ace low one last
+            let mut runs: Vec<Vec<char>> = (len - 1..order.len())
+                .rev()
+                .map(|top| order[top + 1 - len..=top].to_vec())
+                .collect();
+            if self.ace_low {
+                runs.push(order[order.len() - 1..].iter().chain(&order[..len - 1]).copied().collect());
+            }
+            if let Some(run) = runs.into_iter().find(|run| fixed.iter().all(|r| run.contains(r))) {
+                return run;
             }
         }
-        card.unwrap()
-    }
-}
-
-impl<'a> Ord for CamelCard<'a> {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let other_type = other.camel_type();
-        let self_type = self.camel_type();
-        if self_type < other_type {
-            Ordering::Greater
-        } else if other_type == self_type {
-            let stregth = |c: char| -> u32 {
-                match c {
-                    'A' => 14,
-                    'K' => 13,
-                    'Q' => 12,
-                    'J' => 11,
-                    'T' => 10,
-                    '2'..='9' => c.to_digit(10).unwrap(),
-                    _ => panic!("error ordering"),
-                }
-            };
-            for (self_char, other_char) in self.hand.chars().into_iter().zip(other.hand.chars()) {
-                if self_char == other_char {
-                    continue;
+
+        // (count, rank) of every group, largest and strongest first
+        let mut groups: Vec<(usize, char)> = fixed
+            .iter()
+            .counts()
+            .into_iter()
+            .map(|(r, c)| (c, *r))
+            .sorted_by_key(|(c, r)| (*c, self.strength(*r)))
+            .rev()
+            .collect();
+        let strongest_missing = |groups: &[(usize, char)]| {
+            *order.iter().rev().find(|r| groups.iter().all(|(_, g)| g != *r)).unwrap_or(&order[order.len() - 1])
+        };
+
+        let mut wild = wild;
+        match hand_type.groups() {
+            Some(pattern) if !pattern.is_empty() => {
+                for (i, size) in pattern.iter().enumerate() {
+                    if i == groups.len() {
+                        groups.push((0, strongest_missing(&groups)));
+                    }
+                    let add = size.saturating_sub(groups[i].0).min(wild);
+                    groups[i].0 += add;
+                    wild -= add;
                 }
-                if stregth(self_char) > stregth(other_char) {
-                    return Ordering::Greater;
+                groups[0].0 += wild;
+            }
+            _ => {
+                for _ in 0..wild {
+                    groups.push((1, strongest_missing(&groups)));
                 }
-                return Ordering::Less;
             }
-            Ordering::Equal
-        } else {
-            Ordering::Less
         }
+        groups.into_iter().flat_map(|(c, r)| std::iter::repeat_n(r, c)).collect()
+    }
+
+    // hand type in the high bits followed by the card strengths, so comparing
+    // keys compares the types first and then the cards. The hand has to pass
+    // `validate` first
+    fn sort_key(&self, hand: &str) -> u128 {
+        let hand_type = self.hand_type(hand);
+        let rank = match self.categories.iter().position(|c| *c == hand_type) {
+            Some(i) => (self.categories.len() - i) as u128,
+            None => 0,
+        };
+        let bits = self.strength_bits();
+
+        let ranks: Vec<char> = self.cards(hand).iter().map(|(r, _)| *r).collect();
+        let mut strengths: Vec<u128> = ranks.iter().map(|r| self.strength(*r)).collect();
+        if self.tie_break == TieBreak::Grouped {
+            // `is_straight` only knows the non wild ranks
+            let fixed: Vec<char> = ranks.iter().filter(|r| !self.is_wild(**r)).copied().collect();
+            let wheel = matches!(hand_type, CamelCardType::Straight | CamelCardType::StraightFlush)
+                && !self.is_straight(&fixed, ranks.len() - fixed.len(), false);
+            let ace = self.ranks.iter().rev().find(|r| !self.is_wild(**r));
+
+            let resolved = self.resolve_wilds(&fixed, ranks.len() - fixed.len(), hand_type);
+            let counts = resolved.iter().counts();
+            strengths = resolved
+                .iter()
+                .map(|r| {
+                    // the ace of an ace low straight is its weakest card
+                    let strength = if wheel && Some(r) == ace { 0 } else { self.strength(*r) };
+                    (counts[r], strength)
+                })
+                .sorted()
+                .rev()
+                .map(|(_, strength)| strength)
+                .collect();
+        }
+        strengths.into_iter().fold(rank, |key, s| key << bits | s)
     }
 }
 
+#[derive(Debug, Eq, PartialEq)]
+struct CamelCard {
+    bid: u32,
+    key: u128,
+}
+
+impl CamelCard {
+    fn new(hand: &str, bid: u32, spec: &Spec) -> Result<Self, HandError> {
+        spec.validate(hand)?;
+        Ok(CamelCard { bid, key: spec.sort_key(hand) })
+    }
+}
+
+// hands are ordered by their key, the bid only breaks ties between equal hands
+// so the order agrees with `Eq`
+impl Ord for CamelCard {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.key, self.bid).cmp(&(other.key, other.bid))
+    }
+}
+
+impl PartialOrd for CamelCard {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn parse(file: &str, spec: &Spec) -> Result<Vec<CamelCard>, HandError> {
+    file.lines().filter(|l| !l.trim().is_empty()).map(|line| {
+        let bid_error = || HandError::Bid(line.to_owned());
+        let (hand, bid) = line.rsplit_once(' ').ok_or_else(bid_error)?;
+        CamelCard::new(hand, bid.parse().map_err(|_| bid_error())?, spec)
+    }).collect()
+}
+
+fn winnings(mut cards: Vec<CamelCard>) -> u32 {
+    cards.sort();
+    cards.iter().enumerate().map(|(i, v)| v.bid * (i as u32 + 1)).sum()
+}
+
 fn part1() -> u32 {
     let file = fs::read_to_string("src/23/day7/input.txt").unwrap();
-    let lines = file.lines();
-    
-    let mut cards = Vec::new();
-    for line in lines {
-        let (hand, bid) = line.split_once(' ').unwrap();
-        cards.push(CamelCard {
-            hand,
-            bid: bid.parse().unwrap(),
-        });
-    }
-    cards.sort_by(|c1, c2| c1.cmp(c2));
-    cards.iter().enumerate().map(|(i, v)| v.bid * (i as u32 + 1)).sum()
+    winnings(parse(&file, &Spec::camel()).unwrap())
+}
+
+fn part2() -> u32 {
+    let file = fs::read_to_string("src/23/day7/input.txt").unwrap();
+    winnings(parse(&file, &Spec::camel_jokers()).unwrap())
 }
 
 fn main() {
+    // `23-day7 <camel|jokers|poker> <file>` scores other hands, one hand and
+    // its bid per line
+    let args: Vec<String> = env::args().collect();
+    if let [_, name, path] = args.as_slice() {
+        let spec = Spec::named(name).expect("unknown spec");
+        let file = fs::read_to_string(path).unwrap();
+        match parse(&file, &spec) {
+            Ok(cards) => println!("{}", winnings(cards)),
+            Err(e) => eprintln!("{}", e),
+        }
+        return;
+    }
+
     println!("p1: {}", part1());
+    println!("p2: {}", part2());
 }
 
 #[cfg(test)]
 mod test {
     use std::cmp::Ordering;
 
+    use itertools::Itertools;
     use test_case::test_case;
-    use crate::{CamelCardType, CamelCard};
+    use crate::{CamelCardType, CamelCard, HandError, Spec, parse, winnings};
+
+    const EXAMPLE: &str = "32T3K 765\nT55J5 684\nKK677 28\nKTJJT 220\nQQQJA 483";
 
 
     #[test_case("AAAAA", CamelCardType::FiveOfAKind)]
-    #[test_case("AAAAB", CamelCardType::FourOfAKind)]
-    #[test_case("AAA1B", CamelCardType::ThreeOfAKind)]
-    #[test_case("AAABB", CamelCardType::FullHouse)]
-    #[test_case("AA1BB", CamelCardType::TwoPair)]
-    #[test_case("A132A", CamelCardType::OnePair)]
-    #[test_case("B132A", CamelCardType::HighCard)]
+    #[test_case("AAAAK", CamelCardType::FourOfAKind)]
+    #[test_case("AAA2K", CamelCardType::ThreeOfAKind)]
+    #[test_case("AAAKK", CamelCardType::FullHouse)]
+    #[test_case("AA2KK", CamelCardType::TwoPair)]
+    #[test_case("A32QA", CamelCardType::OnePair)]
+    #[test_case("K32QA", CamelCardType::HighCard)]
     fn test_camel_type(hand: &str, camel_type: CamelCardType) {
-        let card = CamelCard { hand, bid: 1, };
-        assert_eq!(card.camel_type(), camel_type);
+        assert_eq!(Spec::camel().hand_type(hand), camel_type);
     }
 
-    #[test_case("AAAAA", "AAAAB", Ordering::Greater)]
+    #[test_case("AAAAA", "AAAAK", Ordering::Greater)]
     #[test_case("AAAAA", "KKKKK", Ordering::Greater)]
     #[test_case("AAKKK", "AAQQQ", Ordering::Greater)]
     #[test_case("KAAKK", "AAAKK", Ordering::Less)]
     fn test_card_cmp(hand1: &str, hand2: &str, ordering: Ordering) {
-        let card1 = CamelCard { hand: hand1, bid: 1 };
-        let card2 = CamelCard { hand: hand2, bid: 1 };
+        let card1 = CamelCard::new(hand1, 1, &Spec::camel()).unwrap();
+        let card2 = CamelCard::new(hand2, 1, &Spec::camel()).unwrap();
+        assert_eq!(card1.cmp(&card2), ordering)
+    }
+
+    #[test]
+    fn test_card_cmp_agrees_with_eq() {
+        let card = |bid| CamelCard::new("KAAKK", bid, &Spec::camel()).unwrap();
+        assert_ne!(card(1), card(2));
+        assert_eq!(card(1).cmp(&card(2)), Ordering::Less);
+        assert_eq!(card(2).cmp(&card(2)), Ordering::Equal);
+        // the hand still decides before the bid
+        assert!(CamelCard::new("AAAKK", 1, &Spec::camel()).unwrap() > card(2));
+    }
+
+    #[test_case("JJJJJ", CamelCardType::FiveOfAKind)]
+    #[test_case("JJJJA", CamelCardType::FiveOfAKind)]
+    #[test_case("QJJQ2", CamelCardType::FourOfAKind)]
+    #[test_case("T55J5", CamelCardType::FourOfAKind)]
+    #[test_case("KK77J", CamelCardType::FullHouse)]
+    #[test_case("KJ677", CamelCardType::ThreeOfAKind)]
+    #[test_case("32T3K", CamelCardType::OnePair)]
+    #[test_case("2345J", CamelCardType::OnePair)]
+    #[test_case("23456", CamelCardType::HighCard)]
+    fn test_camel_type_jokers(hand: &str, camel_type: CamelCardType) {
+        assert_eq!(Spec::camel_jokers().hand_type(hand), camel_type);
+    }
+
+    #[test_case("JJJJJ", "22222", Ordering::Less)]
+    #[test_case("JJJJJ", "JJJJ2", Ordering::Less)]
+    #[test_case("JKKK2", "QQQQ2", Ordering::Less)]
+    #[test_case("J2222", "22223", Ordering::Greater)]
+    fn test_card_cmp_jokers(hand1: &str, hand2: &str, ordering: Ordering) {
+        let card1 = CamelCard::new(hand1, 1, &Spec::camel_jokers()).unwrap();
+        let card2 = CamelCard::new(hand2, 1, &Spec::camel_jokers()).unwrap();
+        assert_eq!(card1.cmp(&card2), ordering)
+    }
+
+    #[test]
+    fn test_winnings() {
+        assert_eq!(winnings(parse(EXAMPLE, &Spec::camel()).unwrap()), 6440);
+        assert_eq!(winnings(parse(EXAMPLE, &Spec::camel_jokers()).unwrap()), 5905);
+    }
+
+    #[test]
+    fn test_sort_synthetic() {
+        let cards = "23456789TJQKA";
+        let mut seed: u64 = 7;
+        let hands: Vec<String> = (0..20_000).map(|_| {
+            (0..5).map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                cards.as_bytes()[(seed >> 33) as usize % cards.len()] as char
+            }).collect()
+        }).collect();
+
+        for spec in [Spec::camel(), Spec::camel_jokers()] {
+            let mut sorted: Vec<(CamelCard, &str)> = hands.iter().map(|h| (CamelCard::new(h, 1, &spec).unwrap(), h.as_str())).collect();
+            sorted.sort();
+            for pair in sorted.windows(2) {
+                let (a, b) = (pair[0].1, pair[1].1);
+                // weaker types sort first, equal types by card strength
+                let (a_type, b_type) = (spec.hand_type(a), spec.hand_type(b));
+                let a_cards: Vec<u128> = a.chars().map(|c| spec.strength(c)).collect();
+                let b_cards: Vec<u128> = b.chars().map(|c| spec.strength(c)).collect();
+                assert!(a_type > b_type || (a_type == b_type && a_cards <= b_cards));
+            }
+        }
+    }
+
+    #[test_case("As Ks Qs Js Ts", CamelCardType::StraightFlush)]
+    #[test_case("5d 4d 3d 2d Ad", CamelCardType::StraightFlush)]
+    #[test_case("9c 9d 9h 9s 2c", CamelCardType::FourOfAKind)]
+    #[test_case("9c 9d 9h 2s 2c", CamelCardType::FullHouse)]
+    #[test_case("Ah 9h 7h 4h 2h", CamelCardType::Flush)]
+    #[test_case("6c 5d 4h 3s 2c", CamelCardType::Straight)]
+    #[test_case("Ac 2d 3h 4s 5c", CamelCardType::Straight)]
+    #[test_case("Kc Ad 2h 3s 4c", CamelCardType::HighCard)]
+    #[test_case("7c 7d 7h Ks 2c", CamelCardType::ThreeOfAKind)]
+    #[test_case("7c 7d Kh Ks 2c", CamelCardType::TwoPair)]
+    #[test_case("7c 7d Qh Ks 2c", CamelCardType::OnePair)]
+    fn test_poker_type(hand: &str, camel_type: CamelCardType) {
+        assert_eq!(Spec::poker().hand_type(hand), camel_type);
+    }
+
+    #[test_case("Ac 2d 3h 4s 5c", "2c 3d 4h 5s 6c", Ordering::Less)]
+    #[test_case("Ac Ad 2h 3s 4c", "Kc Kd Ah Qs Jc", Ordering::Greater)]
+    #[test_case("2c 2d Ah Ks Qc", "Ac 3d 3h Ks Qc", Ordering::Less)]
+    #[test_case("Ah 9h 7h 4h 2h", "As Kd Qh Js Tc", Ordering::Greater)]
+    #[test_case("9c 9d 2h 2s 2c", "3c 3d 3h 4s 4c", Ordering::Less)]
+    fn test_poker_cmp(hand1: &str, hand2: &str, ordering: Ordering) {
+        let card1 = CamelCard::new(hand1, 1, &Spec::poker()).unwrap();
+        let card2 = CamelCard::new(hand2, 1, &Spec::poker()).unwrap();
         assert_eq!(card1.cmp(&card2), ordering)
     }
+
+    #[test]
+    fn test_poker_jokers() {
+        let spec = Spec {
+            ranks: "X23456789TJQKA".chars().collect(),
+            wildcards: vec!['X'],
+            suits: Some("cdhsx".chars().collect()),
+            ..Spec::poker()
+        };
+        let card = |hand| CamelCard::new(hand, 1, &spec).unwrap();
+        assert_eq!(spec.hand_type("Xx 3d 4h 5s 6c"), CamelCardType::Straight);
+        assert_eq!(spec.hand_type("Xx 2d 3h 4s 5c"), CamelCardType::Straight);
+        assert_eq!(spec.hand_type("Xx Ad 2h 3s 4c"), CamelCardType::Straight);
+        assert_eq!(spec.hand_type("Xx 3c 4c 5c 6c"), CamelCardType::StraightFlush);
+        assert_eq!(spec.hand_type("Xx Xx 4h 5s 6c"), CamelCardType::Straight);
+        assert!(card("Xx 3d 4h 5s 6c") > card("Ac 2d 3h 4s 5c"));
+        // the ace low straight made with a joker is still the weakest straight
+        assert!(card("Xx Ad 2h 3s 4c") < card("2c 3d 4h 5s 6c"));
+        assert!(card("Xx 3c 4c 5c 6c") > card("Ac Ad Ah As Kc"));
+
+        // the joker counts as the card it stands in for
+        assert!(card("Xx Kd Qh Js Tc") > card("9c Kd Qh Js Tc"));
+        assert_eq!(card("Xx Kd Qh Js Tc").key, card("Ac Kd Qh Js Tc").key);
+        assert!(card("Xx 3d 4h 5s 6c") > card("2c 3d 4h 5s 6c"));
+        assert_eq!(card("Xx 3d 4h 5s 6c").key, card("3c 4d 5h 6s 7c").key);
+        assert_eq!(card("Xx Xx 4h 5s 6c").key, card("4c 5d 6h 7s 8c").key);
+        assert_eq!(card("Xx 9c 9d 2h 2c").key, card("9c 9h 9s 2d 2s").key);
+        assert!(card("Xx 9c 9d 2h 2c") > card("8c 8h 8s Ad As"));
+        assert_eq!(card("Xx Xx 9d 2h 2c").key, card("2c 2h 2s 2d 9s").key);
+        assert_eq!(card("Xx 9c 9d 2h 3c").key, card("9c 9h 9s 2d 3s").key);
+        assert_eq!(spec.hand_type("Xx Kc 9c 5c 2c"), CamelCardType::Flush);
+        assert_eq!(card("Xx Kc 9c 5c 2c").key, card("Ac Kc 9c 5c 2c").key);
+        // without five of a kind, five jokers are the best straight flush
+        assert_eq!(card("Xx Xx Xx Xx Xx").key, card("Ac Kc Qc Jc Tc").key);
+    }
+
+    #[test]
+    fn test_custom_spec() {
+        // three card hands, jokers and straights over a short alphabet
+        let spec = Spec {
+            ranks: "*1234".chars().collect(),
+            wildcards: vec!['*'],
+            hand_length: 3,
+            categories: vec![CamelCardType::ThreeOfAKind, CamelCardType::Straight, CamelCardType::OnePair],
+            ..Spec::camel()
+        };
+        assert_eq!(spec.hand_type("1*1"), CamelCardType::ThreeOfAKind);
+        assert_eq!(spec.hand_type("1*3"), CamelCardType::Straight);
+        assert_eq!(spec.hand_type("14*"), CamelCardType::OnePair);
+        assert_eq!(spec.hand_type("142"), CamelCardType::HighCard);
+        let card = |hand| CamelCard::new(hand, 1, &spec).unwrap();
+        assert!(card("123") > card("113"));
+        assert!(card("*44") < card("111"));
+    }
+
+    #[test_case("AA1BB", HandError::Card('1'))]
+    #[test_case("AAKK", HandError::Length { expected: 5, found: 4 })]
+    #[test_case("AAKKQQ", HandError::Length { expected: 5, found: 6 })]
+    fn test_validate(hand: &str, error: HandError) {
+        assert_eq!(CamelCard::new(hand, 1, &Spec::camel()).unwrap_err(), error);
+    }
+
+    #[test]
+    fn test_key_width() {
+        // 4 bits per card, so 29 cards fit and 30 don't
+        let spec = |hand_length| Spec { hand_length, ..Spec::camel() };
+        assert!(CamelCard::new(&"A".repeat(29), 1, &spec(29)).is_ok());
+        assert_eq!(
+            CamelCard::new(&"A".repeat(30), 1, &spec(30)).unwrap_err(),
+            HandError::KeyWidth { hand_length: 30, max: 29 }
+        );
+    }
+
+    #[test_case("As Ks Qs Js Tx", HandError::Suit('x'))]
+    #[test_case("As Ks Qs Js T", HandError::MissingSuit)]
+    #[test_case("As Ks Qs Js 1s", HandError::Card('1'))]
+    fn test_validate_poker(hand: &str, error: HandError) {
+        assert_eq!(CamelCard::new(hand, 1, &Spec::poker()).unwrap_err(), error);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(parse("32T3K 765\nT55J5", &Spec::camel()).unwrap_err(), HandError::Bid("T55J5".to_owned()));
+        assert_eq!(parse("32T3K 765\nT55X5 1", &Spec::camel()).unwrap_err(), HandError::Card('X'));
+    }
+
+    // every way of splitting 5 cards into groups of equal cards
+    #[test_case(&[5], CamelCardType::FiveOfAKind)]
+    #[test_case(&[4, 1], CamelCardType::FourOfAKind)]
+    #[test_case(&[3, 2], CamelCardType::FullHouse)]
+    #[test_case(&[3, 1, 1], CamelCardType::ThreeOfAKind)]
+    #[test_case(&[2, 2, 1], CamelCardType::TwoPair)]
+    #[test_case(&[2, 1, 1, 1], CamelCardType::OnePair)]
+    #[test_case(&[1, 1, 1, 1, 1], CamelCardType::HighCard)]
+    fn test_partitions(partition: &[usize], camel_type: CamelCardType) {
+        let hand = hand_from(partition, 0, "23456789TQKA");
+        // the order of the cards doesn't matter
+        let reversed: String = hand.chars().rev().collect();
+        assert_eq!(Spec::camel().hand_type(&hand), camel_type);
+        assert_eq!(Spec::camel().hand_type(&reversed), camel_type);
+    }
+
+    fn hand_from(partition: &[usize], jokers: usize, ranks: &str) -> String {
+        let mut hand: String = partition.iter().zip(ranks.chars()).map(|(n, c)| c.to_string().repeat(*n)).collect();
+        hand.push_str(&"J".repeat(jokers));
+        hand
+    }
+
+    fn partitions(n: usize, max: usize) -> Vec<Vec<usize>> {
+        if n == 0 {
+            return vec![vec![]];
+        }
+        (1..=n.min(max)).rev().flat_map(|first| {
+            partitions(n - first, first).into_iter().map(move |mut rest| {
+                rest.insert(0, first);
+                rest
+            })
+        }).collect()
+    }
+
+    #[test]
+    fn test_partitions_with_jokers() {
+        let ranks = "23456789TQKA";
+        for length in 1..=7 {
+            let spec = Spec { hand_length: length, ..Spec::camel_jokers() };
+            let plain = Spec { hand_length: length, ..Spec::camel() };
+            let strength = |t: CamelCardType| spec.categories.iter().position(|c| *c == t).unwrap();
+
+            for jokers in 0..=length {
+                for partition in partitions(length - jokers, length) {
+                    let hand = hand_from(&partition, jokers, ranks);
+                    // try every joker as one of the cards in the hand or a new one
+                    let options: Vec<char> = ranks.chars().take(partition.len() + 1).collect();
+                    let best = (0..jokers)
+                        .map(|_| options.iter())
+                        .multi_cartesian_product()
+                        .map(|replacement| {
+                            let mut replacement = replacement.into_iter();
+                            let replaced: String = hand.chars().map(|c| if c == 'J' { *replacement.next().unwrap() } else { c }).collect();
+                            plain.hand_type(&replaced)
+                        })
+                        .min_by_key(|t| strength(*t))
+                        .unwrap_or_else(|| plain.hand_type(&hand));
+                    assert_eq!(spec.hand_type(&hand), best, "{}", hand);
+                }
+            }
+        }
+    }
 }
@@ -1,108 +1,426 @@
-use std::{
-    collections::{BTreeMap, HashMap, LinkedList},
-    fs, u32::MAX,
-};
+use std::{collections::HashMap, env, fmt::Write, fs};
 
-use num::Integer;
+use num::{BigInt, BigUint, Integer, One, Zero};
 
-fn part1() -> u32 {
-    let file = fs::read_to_string("src/23/day8/input.txt").unwrap();
-    let (instructions, nodes) = file.split_once("\n\n").unwrap();
+// nodes are numbered in the order they are listed
+struct Network<'a> {
+    names: Vec<&'a str>,
+    ids: HashMap<&'a str, usize>,
+    // left and right node of every node
+    next: Vec<[usize; 2]>,
+    // 0 for left and 1 for right
+    instructions: Vec<usize>,
+}
 
-    let mut node_map: HashMap<&str, (&str, &str)> = HashMap::new();
+impl<'a> Network<'a> {
+    fn new(file: &'a str) -> Self {
+        let (instructions, nodes) = file.split_once("\n\n").unwrap();
 
-    for node in nodes.split('\n') {
-        if node == "" {
-            continue;
+        let mut lines: Vec<(&str, &str, &str)> = Vec::new();
+        for node in nodes.split('\n') {
+            if node.is_empty() {
+                continue;
+            }
+            let (id, rest) = node.split_once(" = (").unwrap();
+            let (l, mut r) = rest.split_once(", ").unwrap();
+            r = r.trim_end_matches(')');
+            lines.push((id, l, r));
         }
-        let (id, rest) = node.split_once(" = (").unwrap();
-        let (l, mut r) = rest.split_once(", ").unwrap();
-        r = r.trim_end_matches(')');
 
-        node_map.insert(id, (l, r));
+        let names: Vec<&str> = lines.iter().map(|(id, _, _)| *id).collect();
+        let ids: HashMap<&str, usize> = names.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+        let next = lines.iter().map(|(_, l, r)| [ids[l], ids[r]]).collect();
+        let instructions = instructions
+            .trim()
+            .chars()
+            .map(|c| match c {
+                'L' => 0,
+                'R' => 1,
+                _ => panic!("unsupported direction"),
+            })
+            .collect();
+
+        Network { names, ids, next, instructions }
+    }
+
+    fn step(&self, node: usize, index: usize) -> usize {
+        self.next[node][self.instructions[index]]
+    }
+
+    fn starts(&self) -> Vec<usize> {
+        (0..self.names.len()).filter(|n| self.names[*n].ends_with('A')).collect()
+    }
+
+    fn is_end(&self, node: usize) -> bool {
+        self.names[node].ends_with('Z')
     }
 
-    let mut current = "AAA";
-    let mut pair = node_map.get(current).unwrap();
-    let mut count = 0;
-    loop {
-        for c in instructions.chars() {
-            if current == "ZZZ" {
-                return count;
+    // walk until a (node, instruction index) state repeats, which is where
+    // the walk starts to loop
+    fn ghost_cycle(&self, start: usize, is_end: impl Fn(usize) -> bool) -> GhostCycle {
+        let len = self.instructions.len();
+        let mut seen: Vec<Option<u128>> = vec![None; self.names.len() * len];
+        let mut ends = Vec::new();
+
+        let mut current = start;
+        let mut t: u128 = 0;
+        loop {
+            let index = (t % len as u128) as usize;
+            if let Some(first) = seen[current * len + index] {
+                let (prefix, hits) = ends.into_iter().partition(|e| *e < first);
+                return GhostCycle { prefix, start: first, length: t - first, hits };
             }
-            match c {
-                'L' => {
-                    current = pair.0;
-                    pair = node_map.get(current).unwrap();
-                }
-                'R' => {
-                    current = pair.1;
-                    pair = node_map.get(current).unwrap();
-                }
-                _ => panic!("unsupported direction"),
+            seen[current * len + index] = Some(t);
+            if is_end(current) {
+                ends.push(t);
             }
-            count = count + 1;
+            current = self.step(current, index);
+            t += 1;
         }
     }
-}
 
-fn find_steps(node: &str, node_map: &HashMap<&str, (&str, &str)>, instructions: &str) -> u32 {
-    let mut current = node;
-    let mut pair = node_map.get(current).unwrap();
-    let mut count = 0;
-    loop {
-        for c in instructions.chars() {
-            if current.chars().last().unwrap() == 'Z' {
-                dbg!(count);
-                return count;
+    // edges (from, to) a ghost keeps walking once it loops
+    fn cycle_edges(&self, start: usize) -> Vec<(usize, usize)> {
+        let cycle = self.ghost_cycle(start, |_| false);
+        let len = self.instructions.len() as u128;
+        let mut current = start;
+        let mut edges = Vec::new();
+        for t in 0..cycle.start + cycle.length {
+            let next = self.step(current, (t % len) as usize);
+            if t >= cycle.start {
+                edges.push((current, next));
             }
-            match c {
-                'L' => {
-                    current = pair.0;
-                    pair = node_map.get(current).unwrap();
-                }
-                'R' => {
-                    current = pair.1;
-                    pair = node_map.get(current).unwrap();
+            current = next;
+        }
+        edges.sort();
+        edges.dedup();
+        edges
+    }
+
+    // for every edge the ghosts (by their index in `starts`) looping over it
+    fn ghost_edges(&self) -> HashMap<(usize, usize), Vec<usize>> {
+        let mut ghosts: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (ghost, start) in self.starts().into_iter().enumerate() {
+            for edge in self.cycle_edges(start) {
+                ghosts.entry(edge).or_default().push(ghost);
+            }
+        }
+        ghosts
+    }
+
+    fn kind(&self, node: usize) -> &'static str {
+        if self.names[node].ends_with('A') {
+            "start"
+        } else if self.is_end(node) {
+            "end"
+        } else {
+            "node"
+        }
+    }
+
+    // start nodes are green, end nodes red and every ghost's loop gets its
+    // own edge colour
+    fn to_dot(&self) -> String {
+        const COLOURS: [&str; 6] = ["blue", "orange", "purple", "brown", "cyan", "magenta"];
+        let ghosts = self.ghost_edges();
+
+        let mut dot = String::from("digraph network {\n");
+        for (node, name) in self.names.iter().enumerate() {
+            let id = dot_id(name);
+            match self.kind(node) {
+                "start" => writeln!(dot, "  {} [style=filled, fillcolor=green];", id).unwrap(),
+                "end" => writeln!(dot, "  {} [style=filled, fillcolor=red];", id).unwrap(),
+                _ => writeln!(dot, "  {};", id).unwrap(),
+            }
+        }
+        for (node, next) in self.next.iter().enumerate() {
+            for (label, to) in ["L", "R"].iter().zip(next) {
+                let (from, to_id) = (dot_id(self.names[node]), dot_id(self.names[*to]));
+                match ghosts.get(&(node, *to)) {
+                    Some(g) => writeln!(
+                        dot,
+                        "  {} -> {} [label={}, color=\"{}\", penwidth=2];",
+                        from,
+                        to_id,
+                        label,
+                        g.iter().map(|i| COLOURS[i % COLOURS.len()]).collect::<Vec<_>>().join(":"),
+                    ).unwrap(),
+                    None => writeln!(dot, "  {} -> {} [label={}];", from, to_id, label).unwrap(),
                 }
-                _ => panic!("unsupported direction"),
             }
-            count = count + 1;
         }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn to_graphml(&self) -> String {
+        let ghosts = self.ghost_edges();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        xml.push_str("  <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n");
+        xml.push_str("  <key id=\"direction\" for=\"edge\" attr.name=\"direction\" attr.type=\"string\"/>\n");
+        xml.push_str("  <key id=\"ghosts\" for=\"edge\" attr.name=\"ghosts\" attr.type=\"string\"/>\n");
+        xml.push_str("  <graph id=\"network\" edgedefault=\"directed\">\n");
+        for (node, name) in self.names.iter().enumerate() {
+            writeln!(xml, "    <node id=\"{}\"><data key=\"kind\">{}</data></node>", xml_escape(name), self.kind(node)).unwrap();
+        }
+        for (node, next) in self.next.iter().enumerate() {
+            for (label, to) in ["L", "R"].iter().zip(next) {
+                let ghost_list = ghosts
+                    .get(&(node, *to))
+                    .map(|g| g.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(","))
+                    .unwrap_or_default();
+                writeln!(
+                    xml,
+                    "    <edge source=\"{}\" target=\"{}\"><data key=\"direction\">{}</data><data key=\"ghosts\">{}</data></edge>",
+                    xml_escape(self.names[node]), xml_escape(self.names[*to]), label, ghost_list,
+                ).unwrap();
+            }
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
     }
 }
 
-fn part2() -> u64 {
+// names can start with a digit, which a bare DOT ID can't, so every ID is quoted
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn xml_escape(name: &str) -> String {
+    name.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn part1() -> u32 {
     let file = fs::read_to_string("src/23/day8/input.txt").unwrap();
-    let (instructions, nodes) = file.split_once("\n\n").unwrap();
+    let network = Network::new(&file);
 
-    let mut node_map: HashMap<&str, (&str, &str)> = HashMap::new();
+    let mut current = network.ids["AAA"];
+    let end = network.ids["ZZZ"];
+    for count in 0.. {
+        if current == end {
+            return count;
+        }
+        current = network.step(current, count as usize % network.instructions.len());
+    }
+    unreachable!()
+}
 
-    for node in nodes.split('\n') {
-        if node == "" {
-            continue;
+// the walk of one ghost, it is at an end node after `t` steps if `t` is in
+// `prefix` or `t >= start` and `t` is one of `hits` plus a multiple of `length`
+#[derive(Debug, PartialEq, Eq)]
+struct GhostCycle {
+    prefix: Vec<u128>,
+    start: u128,
+    length: u128,
+    hits: Vec<u128>,
+}
+
+impl GhostCycle {
+    fn at_end(&self, t: u128) -> bool {
+        if t < self.start {
+            return self.prefix.contains(&t);
         }
-        let (id, rest) = node.split_once(" = (").unwrap();
-        let (l, mut r) = rest.split_once(", ").unwrap();
-        r = r.trim_end_matches(')');
+        let offset = (t - self.start) % self.length;
+        self.hits.contains(&(self.start + offset))
+    }
+}
 
-        node_map.insert(id, (l, r));
+// x = a1 (mod n1) and x = a2 (mod n2) as one x = a (mod n), moduli don't
+// have to be coprime. The combined modulus grows with every ghost, so this
+// works on big integers
+fn crt(a1: &BigInt, n1: &BigInt, a2: &BigInt, n2: &BigInt) -> Option<(BigInt, BigInt)> {
+    let egcd = n1.extended_gcd(n2);
+    let diff = a2 - a1;
+    if !diff.is_multiple_of(&egcd.gcd) {
+        return None;
     }
-    let starts: Vec<_> = node_map
-        .iter()
-        .filter(|(k, _)| k.chars().last().unwrap() == 'A')
-        .collect();
-    dbg!(&starts);
+    let lcm = n1 / &egcd.gcd * n2;
+    // n1 * x = diff (mod n2)
+    let m = n2 / &egcd.gcd;
+    let k = (diff / &egcd.gcd * egcd.x).mod_floor(&m);
+    let a = (a1 + n1 * k).mod_floor(&lcm);
+    Some((a, lcm))
+}
 
-    let mut total: u64 = 21389;
-    for start in starts {
-        total = total.lcm(&(find_steps(start.0, &node_map, instructions) as u64));
+// first step where every ghost is at an end node at the same time
+fn synchronise(cycles: &[GhostCycle]) -> Option<BigUint> {
+    // before every ghost is looping check the steps one by one
+    let looping = cycles.iter().map(|c| c.start).max()?;
+    if let Some(t) = (0..looping).find(|t| cycles.iter().all(|c| c.at_end(*t))) {
+        return Some(t.into());
     }
-    total
+
+    // afterwards each ghost is at an end at one of its hits modulo its length
+    let mut congruences: Vec<(BigInt, BigInt)> = vec![(BigInt::zero(), BigInt::one())];
+    for cycle in cycles {
+        let length = BigInt::from(cycle.length);
+        let mut next: Vec<(BigInt, BigInt)> = congruences
+            .iter()
+            .flat_map(|(a, n)| {
+                let length = &length;
+                cycle.hits.iter().filter_map(move |h| crt(a, n, &BigInt::from(h % cycle.length), length))
+            })
+            .collect();
+        next.sort();
+        next.dedup();
+        congruences = next;
+    }
+
+    let looping = BigInt::from(looping);
+    congruences
+        .into_iter()
+        .map(|(a, n)| {
+            // smallest t >= looping with t = a (mod n)
+            let below = &looping - looping.mod_floor(&n) + a;
+            if below >= looping { below } else { below + n }
+        })
+        .min()
+        .map(|t| t.to_biguint().unwrap())
+}
+
+fn part2() -> Option<BigUint> {
+    let file = fs::read_to_string("src/23/day8/input.txt").unwrap();
+    let network = Network::new(&file);
+
+    let cycles: Vec<GhostCycle> = network
+        .starts()
+        .into_iter()
+        .map(|start| network.ghost_cycle(start, |n| network.is_end(n)))
+        .collect();
+
+    synchronise(&cycles)
 }
 
 
 fn main() {
+    // `23-day8 <dot|graphml>` prints the network instead
+    let args: Vec<String> = env::args().collect();
+    if let [_, format] = args.as_slice() {
+        let file = fs::read_to_string("src/23/day8/input.txt").unwrap();
+        let network = Network::new(&file);
+        match format.as_str() {
+            "dot" => print!("{}", network.to_dot()),
+            "graphml" => print!("{}", network.to_graphml()),
+            _ => eprintln!("unknown format: {}", format),
+        }
+        return;
+    }
+
     println!("p1: {}", part1());
-    println!("p2: {}", part2());
+    match part2() {
+        Some(steps) => println!("p2: {}", steps),
+        None => println!("p2: the ghosts never reach the end nodes together"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use num::{BigInt, BigUint};
+
+    use crate::{crt, synchronise, GhostCycle, Network};
+
+    fn solve(file: &str) -> Option<BigUint> {
+        let network = Network::new(file);
+        let cycles: Vec<GhostCycle> = network
+            .starts()
+            .into_iter()
+            .map(|start| network.ghost_cycle(start, |n| network.is_end(n)))
+            .collect();
+        synchronise(&cycles)
+    }
+
+    #[test]
+    fn test_example() {
+        let file = "LR\n\n11A = (11B, XXX)\n11B = (XXX, 11Z)\n11Z = (11B, XXX)\n22A = (22B, XXX)\n22B = (22C, 22C)\n22C = (22Z, 22Z)\n22Z = (22B, 22B)\nXXX = (XXX, XXX)\n";
+        assert_eq!(solve(file), Some(6u32.into()));
+
+        let network = Network::new(file);
+        let cycle = network.ghost_cycle(network.ids["22A"], |n| network.is_end(n));
+        assert_eq!(cycle, GhostCycle { prefix: vec![], start: 1, length: 6, hits: vec![3, 6] });
+    }
+
+    #[test]
+    fn test_export() {
+        let network = Network::new("L\n\nAA = (BB, ZZ)\nBB = (ZZ, AA)\nZZ = (BB, BB)\n");
+        assert_eq!(network.next, vec![[1, 2], [2, 0], [1, 1]]);
+        assert_eq!(network.cycle_edges(0), vec![(1, 2), (2, 1)]);
+
+        let dot = network.to_dot();
+        assert!(dot.contains("  \"AA\" [style=filled, fillcolor=green];\n"));
+        assert!(dot.contains("  \"ZZ\" [style=filled, fillcolor=red];\n"));
+        assert!(dot.contains("  \"BB\" -> \"ZZ\" [label=L, color=\"blue\", penwidth=2];\n"));
+        assert!(dot.contains("  \"AA\" -> \"ZZ\" [label=R];\n"));
+
+        let graphml = network.to_graphml();
+        assert!(graphml.contains("<node id=\"BB\"><data key=\"kind\">node</data></node>"));
+        assert!(graphml.contains("<edge source=\"ZZ\" target=\"BB\"><data key=\"direction\">L</data><data key=\"ghosts\">0</data></edge>"));
+    }
+
+    #[test]
+    fn test_export_names() {
+        // names starting with a digit, or holding quotes and markup
+        let network = Network::new("L\n\n11A = (1\"Z, <&>)\n1\"Z = (11A, 11A)\n<&> = (<&>, <&>)\n");
+        let dot = network.to_dot();
+        assert!(dot.contains("  \"11A\" [style=filled, fillcolor=green];\n"));
+        assert!(dot.contains("  \"1\\\"Z\" [style=filled, fillcolor=red];\n"));
+        assert!(dot.contains("  \"11A\" -> \"<&>\" [label=R];\n"));
+
+        let graphml = network.to_graphml();
+        assert!(graphml.contains("<node id=\"1&quot;Z\"><data key=\"kind\">end</data></node>"));
+        assert!(graphml.contains("<edge source=\"11A\" target=\"&lt;&amp;&gt;\">"));
+    }
+
+    #[test]
+    fn test_offset_cycles() {
+        // 1A reaches 1Z at 3, 6, 9, ... and 2A reaches 2Z at 4, 6, 8, ...
+        // so the first ends can't just be multiplied together
+        let file = "L\n\n1A = (1B, 1B)\n1B = (1C, 1C)\n1C = (1Z, 1Z)\n1Z = (1D, 1D)\n1D = (1E, 1E)\n1E = (1Z, 1Z)\n2A = (2B, 2B)\n2B = (2C, 2C)\n2C = (2D, 2D)\n2D = (2Z, 2Z)\n2Z = (2E, 2E)\n2E = (2Z, 2Z)\n";
+        assert_eq!(solve(file), Some(6u32.into()));
+    }
+
+    #[test]
+    fn test_prefix_only() {
+        // 1Z is only passed once, at step 1, which is when 2A is at 2Z
+        let file = "L\n\n1A = (1Z, 1Z)\n1Z = (1B, 1B)\n1B = (1B, 1B)\n2A = (2Z, 2Z)\n2Z = (2A, 2A)\n";
+        assert_eq!(solve(file), Some(1u32.into()));
+    }
+
+    #[test]
+    fn test_never() {
+        // 1A is at an end on odd steps and 2A on even steps
+        let file = "L\n\n1A = (1Z, 1Z)\n1Z = (1A, 1A)\n2A = (2B, 2B)\n2B = (2Z, 2Z)\n2Z = (2B, 2B)\n";
+        assert_eq!(solve(file), None);
+    }
+
+    #[test_case(2, 3, 3, 5, Some((8, 15)))]
+    #[test_case(2, 4, 0, 6, Some((6, 12)))]
+    #[test_case(1, 4, 0, 6, None)]
+    #[test_case(0, 1, 4, 7, Some((4, 7)))]
+    fn test_crt(a1: u128, n1: u128, a2: u128, n2: u128, expected: Option<(u128, u128)>) {
+        let big = BigInt::from;
+        assert_eq!(crt(&big(a1), &big(n1), &big(a2), &big(n2)), expected.map(|(a, n)| (big(a), big(n))));
+    }
+
+    #[test]
+    fn test_synchronise_past_u128() {
+        // two coprime loops of about 2^70 steps, they first meet well past u128::MAX
+        let (l1, l2): (u128, u128) = (1 << 70, (1 << 70) - 1);
+        let cycles = [
+            GhostCycle { prefix: vec![], start: 0, length: l1, hits: vec![l1 - 1] },
+            GhostCycle { prefix: vec![], start: 0, length: l2, hits: vec![l2 - 2] },
+        ];
+        let t = synchronise(&cycles).unwrap();
+        assert!(t > BigUint::from(u128::MAX));
+        assert!(t < BigUint::from(l1) * l2);
+        assert_eq!(&t % l1, BigUint::from(l1 - 1));
+        assert_eq!(&t % l2, BigUint::from(l2 - 2));
+    }
 }
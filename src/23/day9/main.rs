@@ -1,50 +1,136 @@
-use std::fs;
+use std::{fmt, fs};
 
-use itertools::Itertools;
+use num::{BigInt, BigRational, One, Signed, Zero};
 
 struct Sequence {
     nums: Vec<i64>,
 }
 
+// coefficients[i] belongs to x^i where x is the offset from the first number.
+// `determined` is false when the differences never reach a row of zeros, then
+// this is only the lowest degree polynomial through the numbers
+#[derive(Debug, PartialEq)]
+struct Polynomial {
+    coefficients: Vec<BigRational>,
+    determined: bool,
+}
+
+impl Polynomial {
+    fn degree(&self) -> usize {
+        self.coefficients.iter().rposition(|c| !c.is_zero()).unwrap_or(0)
+    }
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms: Vec<(usize, &BigRational)> = self.coefficients.iter().enumerate().rev().filter(|(_, c)| !c.is_zero()).collect();
+        if terms.is_empty() {
+            return write!(f, "0");
+        }
+        for (n, (i, c)) in terms.into_iter().enumerate() {
+            match (n, c.is_negative()) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            let c = c.abs();
+            let variable = match i {
+                0 => String::new(),
+                1 => "x".to_owned(),
+                _ => format!("x^{}", i),
+            };
+            if !c.is_one() || i == 0 {
+                if c.is_integer() {
+                    write!(f, "{}", c)?;
+                } else {
+                    write!(f, "({})", c)?;
+                }
+            }
+            write!(f, "{}", variable)?;
+        }
+        Ok(())
+    }
+}
+
 impl Sequence {
     // estimate next value in sequence
     fn estimate_next(&self) -> i64 {
-        let mut total = *self.nums.last().unwrap();
-        let mut current = Sequence::diff(self);
+        self.extrapolate(self.nums.len() as i64).try_into().unwrap()
+    }
+
+    fn estimate_previous(&self) -> i64 {
+        self.extrapolate(-1).try_into().unwrap()
+    }
+
+    // first value of every difference row: A0, B0, C0, ...
+    fn forward_differences(&self) -> Vec<BigInt> {
+        let mut row: Vec<BigInt> = self.nums.iter().map(|n| BigInt::from(*n)).collect();
+        let mut firsts = Vec::new();
+        while let Some(first) = row.first() {
+            firsts.push(first.clone());
+            row = row.windows(2).map(|n| &n[1] - &n[0]).collect();
+        }
+        firsts
+    }
 
-        loop {
-            total = total + current.nums.last().unwrap();
-            if current.nums.iter().all_equal() {
-                return total;
+    // value at offset `k` from the first number, negative offsets go
+    // backwards. Newton's forward difference formula:
+    // f(k) = A0 + C(k, 1) * B0 + C(k, 2) * C0 + ...
+    fn extrapolate(&self, k: i64) -> BigInt {
+        let k = BigInt::from(k);
+        let mut binomial = BigInt::one();
+        let mut total = BigInt::zero();
+        for (j, difference) in self.forward_differences().iter().enumerate() {
+            if j > 0 {
+                // C(k, j) = C(k, j - 1) * (k - j + 1) / j, always divides evenly
+                binomial = binomial * (&k - (j as i64 - 1)) / j as i64;
             }
-            current = Sequence::diff(&current);
+            total += &binomial * difference;
         }
+        total
     }
 
-    fn estimate_previous(&self) -> i64 {
-        let mut total = *self.nums.first().unwrap();
-        let mut current = Sequence::diff(self);
-        let mut iter = 1;
-        loop {
-            let first = current.nums.first().unwrap();
-            total = if iter % 2 == 0 {
-                total + first
-            } else {
-                total - first
-            };
-            iter = iter + 1;
+    // f(x) = A0 + B0 * x + C0 * x(x - 1) / 2! + ... expanded into powers of x
+    fn polynomial(&self) -> Polynomial {
+        let differences = self.forward_differences();
+        let determined = self.is_polynomial();
 
-            if current.nums.iter().all_equal() {
-                return total;
+        let mut coefficients = vec![BigRational::zero(); differences.len().max(1)];
+        // x(x - 1)...(x - j + 1) as coefficients of powers of x
+        let mut falling: Vec<BigInt> = vec![BigInt::one()];
+        let mut factorial = BigInt::one();
+        for (j, difference) in differences.iter().enumerate() {
+            if j > 0 {
+                factorial *= j;
+                let shift = BigInt::from(j - 1);
+                let mut next = vec![BigInt::zero(); falling.len() + 1];
+                for (i, c) in falling.iter().enumerate() {
+                    next[i + 1] += c;
+                    next[i] -= c * &shift;
+                }
+                falling = next;
+            }
+            for (i, c) in falling.iter().enumerate() {
+                coefficients[i] += BigRational::new(c * difference, factorial.clone());
             }
-            current = Sequence::diff(&current);
         }
+
+        let mut polynomial = Polynomial { coefficients, determined };
+        polynomial.coefficients.truncate(polynomial.degree() + 1);
+        polynomial
     }
 
-    fn diff(sequence: &Self) -> Self {
-        Sequence {
-            nums: sequence.nums.windows(2).map(|n| n[1] - n[0]).collect(),
+    // some difference row has at least one number and they are all zero
+    fn is_polynomial(&self) -> bool {
+        let mut row: Vec<BigInt> = self.nums.iter().map(|n| BigInt::from(*n)).collect();
+        while !row.is_empty() {
+            if row.iter().all(|n| n.is_zero()) {
+                return true;
+            }
+            row = row.windows(2).map(|n| &n[1] - &n[0]).collect();
         }
+        false
     }
 
     fn new(line: &str) -> Self {
@@ -61,7 +147,7 @@ fn part1() -> i64 {
     let sequences: Vec<Sequence> = fs::read_to_string("src/23/day9/input.txt")
         .unwrap()
         .lines()
-        .map(|l| Sequence::new(l))
+        .map(Sequence::new)
         .collect();
 
     sequences.iter().map(|s| s.estimate_next()).sum()
@@ -71,7 +157,7 @@ fn part2() -> i64 {
     let sequences: Vec<Sequence> = fs::read_to_string("src/23/day9/input.txt")
         .unwrap()
         .lines()
-        .map(|l| Sequence::new(l))
+        .map(Sequence::new)
         .collect();
 
     sequences.iter().map(|s| s.estimate_previous()).sum()
@@ -80,14 +166,36 @@ fn part2() -> i64 {
 fn main() {
     println!("p1: {}", part1());
     println!("p2: {}", part2());
-    // sum all estimates
+
+    let sequences: Vec<Sequence> = fs::read_to_string("src/23/day9/input.txt")
+        .unwrap()
+        .lines()
+        .map(Sequence::new)
+        .collect();
+    for (i, sequence) in sequences.iter().enumerate() {
+        let polynomial = sequence.polynomial();
+        if !polynomial.determined {
+            println!("line {}: too few numbers to be sure, best guess {}", i + 1, polynomial);
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use test_case::test_case;
 
-    use crate::Sequence;
+    use num::{BigInt, BigRational, Zero};
+
+    use crate::{Polynomial, Sequence};
+
+    fn eval(polynomial: &Polynomial, x: i64) -> BigRational {
+        let x = BigRational::from_integer(BigInt::from(x));
+        polynomial.coefficients.iter().rev().fold(BigRational::zero(), |acc, c| acc * &x + c)
+    }
+
+    fn ratio(n: i64, d: i64) -> BigRational {
+        BigRational::new(BigInt::from(n), BigInt::from(d))
+    }
 
     // A0 A1 A2 A3 A4
     //  B0 B1 B2 B3
@@ -106,4 +214,64 @@ mod test {
         assert_eq!(Sequence::new(nums).estimate_next(), next);
         assert_eq!(Sequence::new(nums).estimate_previous(), previous);
     }
+
+    #[test_case("10 13 16 21 30 45", 0, 10)]
+    #[test_case("10 13 16 21 30 45", 5, 45)]
+    #[test_case("10 13 16 21 30 45", 7, 101)]
+    #[test_case("10 13 16 21 30 45", -2, -4)]
+    #[test_case("1 3 6 10 15 21", -3, 1)]
+    #[test_case("1 3 6 10 15 21", 99, 5050)]
+    #[test_case("0 3 6 9 12 15", -10, -30)]
+    #[test_case("7", 1000, 7)]
+    fn test_extrapolate(nums: &str, k: i64, value: i64) {
+        assert_eq!(Sequence::new(nums).extrapolate(k), BigInt::from(value));
+    }
+
+    #[test]
+    fn test_extrapolate_big() {
+        // n^3, far past what fits in an i64
+        let sequence = Sequence::new("0 1 8 27");
+        let k = 10_000_000_000i64;
+        assert_eq!(sequence.extrapolate(k), BigInt::from(k).pow(3));
+        assert_eq!(sequence.extrapolate(-k), -BigInt::from(k).pow(3));
+    }
+
+    #[test_case("10 13 16 21 30 45", "(1/3)x^3 - x^2 + (11/3)x + 10", 3)]
+    #[test_case("1 3 6 10 15 21", "(1/2)x^2 + (3/2)x + 1", 2)]
+    #[test_case("0 3 6 9 12 15", "3x", 1)]
+    #[test_case("-4 -4 -4", "-4", 0)]
+    #[test_case("0 0 0", "0", 0)]
+    fn test_polynomial(nums: &str, model: &str, degree: usize) {
+        let sequence = Sequence::new(nums);
+        let polynomial = sequence.polynomial();
+        assert!(polynomial.determined);
+        assert_eq!(polynomial.to_string(), model);
+        assert_eq!(polynomial.degree(), degree);
+        for (x, n) in sequence.nums.iter().enumerate() {
+            assert_eq!(eval(&polynomial, x as i64), ratio(*n, 1));
+        }
+        assert_eq!(eval(&polynomial, -1), ratio(sequence.estimate_previous(), 1));
+    }
+
+    #[test_case("1 2 4 8 16")]
+    #[test_case("3 1")]
+    #[test_case("5")]
+    fn test_polynomial_undetermined(nums: &str) {
+        let sequence = Sequence::new(nums);
+        let polynomial = sequence.polynomial();
+        assert!(!polynomial.determined);
+        assert_eq!(polynomial.degree(), sequence.nums.len() - 1);
+        // still goes through every number and the estimates don't hang
+        for (x, n) in sequence.nums.iter().enumerate() {
+            assert_eq!(eval(&polynomial, x as i64), ratio(*n, 1));
+        }
+        assert_eq!(eval(&polynomial, sequence.nums.len() as i64), ratio(sequence.estimate_next(), 1));
+    }
+
+    #[test]
+    fn test_polynomial_half() {
+        // x(x - 1) / 2 isn't integer coefficients but always an integer value
+        let polynomial = Sequence::new("0 0 1 3 6").polynomial();
+        assert_eq!(polynomial.coefficients, vec![ratio(0, 1), ratio(-1, 2), ratio(1, 2)]);
+    }
 }